@@ -0,0 +1,130 @@
+//! Embeds the git commit, build time, and AWS SDK crate versions for `aws-sso-env --version`.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The crates whose versions `--version --verbose` reports, as resolved in `Cargo.lock`.
+const SDK_CRATES: &[&str] = &[
+    "aws-config",
+    "aws-sdk-sso",
+    "aws-sdk-ssooidc",
+    "aws-sdk-sts",
+    "aws-smithy-client",
+    "aws-types",
+];
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let manifest_dir = Path::new(manifest_dir.as_str());
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let git_dir = manifest_dir.join(".git");
+
+    // a new commit changes the branch's ref rather than HEAD itself
+    if let Ok(head) = std::fs::read_to_string(git_dir.join("HEAD")) {
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            println!(
+                "cargo:rerun-if-changed={}",
+                git_dir.join(reference).display()
+            );
+        }
+    }
+
+    println!("cargo:rustc-env=AWS_SSO_ENV_COMMIT={}", commit());
+    println!("cargo:rustc-env=AWS_SSO_ENV_BUILT_AT={}", built_at());
+    println!(
+        "cargo:rustc-env=AWS_SSO_ENV_SDK_VERSIONS={}",
+        sdk_versions(manifest_dir)
+    );
+}
+
+/// The abbreviated hash of the commit being built, suffixed with `-dirty` if there are uncommitted changes, or
+/// `unknown` outside of a git checkout.
+fn commit() -> String {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(hash) if !hash.is_empty() => match git(&["status", "--porcelain"]) {
+            Some(changes) if !changes.is_empty() => format!("{}-dirty", hash),
+            _ => hash,
+        },
+        _ => "unknown".into(),
+    }
+}
+
+/// The time of the build as an RFC3339 timestamp in UTC, taken from `SOURCE_DATE_EPOCH` for reproducible builds.
+fn built_at() -> String {
+    let epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default()
+        });
+
+    let (days, seconds) = (epoch / 86_400, epoch % 86_400);
+
+    // the civil date of a count of days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// The version of each of the [`SDK_CRATES`] resolved in `Cargo.lock`, as comma-separated `name version` pairs.
+fn sdk_versions(manifest_dir: &Path) -> String {
+    let lock = std::fs::read_to_string(manifest_dir.join("Cargo.lock")).unwrap_or_default();
+
+    SDK_CRATES
+        .iter()
+        .map(|name| {
+            let name_line = format!("name = \"{}\"", name);
+
+            let version = lock
+                .lines()
+                .skip_while(|line| *line != name_line)
+                .nth(1)
+                .and_then(|line| line.strip_prefix("version = \""))
+                .and_then(|version| version.strip_suffix('"'))
+                .unwrap_or("unknown");
+
+            format!("{} {}", name, version)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -0,0 +1,119 @@
+//! Discovery of the accounts and roles an SSO token grants access to.
+
+use aws_sdk_sso::{Client as SsoClient, SdkError};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::proxy::proxy_failure;
+use crate::{CachedSsoToken, SsoProfile};
+
+/// An account which the SSO user has access to.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub account_id: String,
+    pub account_name: Option<String>,
+    pub email_address: Option<String>,
+}
+
+/// A role which the SSO user may assume within an account.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountRole {
+    pub account_id: String,
+    pub role_name: String,
+}
+
+/// List the roles the token grants access to within the profile's account, following every page of the SSO
+/// `ListAccountRoles` API.
+pub async fn list_account_roles(
+    client: &SsoClient,
+    profile: &SsoProfile,
+    token: &CachedSsoToken,
+) -> Result<Vec<AccountRole>> {
+    let account_id = profile.account_id()?;
+
+    let mut roles = Vec::new();
+    let mut next_token = None;
+
+    loop {
+        let response = client
+            .list_account_roles()
+            .account_id(account_id)
+            .access_token(token.access_token.clone())
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(|e| match e {
+                SdkError::ServiceError { err, .. } if err.is_unauthorized_exception() => {
+                    Error::CacheTokenUnauthorized(profile.profile_name.clone())
+                }
+                e => proxy_failure(&e).unwrap_or_else(|| Error::SsoApi {
+                    operation: "ListAccountRoles",
+                    source: e.into(),
+                }),
+            })?;
+
+        for role in response.role_list.unwrap_or_default() {
+            roles.push(AccountRole {
+                account_id: role.account_id.unwrap_or_else(|| account_id.into()),
+                role_name: role
+                    .role_name
+                    .ok_or(Error::IncompleteResponse("a role name"))?,
+            });
+        }
+
+        next_token = response.next_token.filter(|token| !token.is_empty());
+
+        if next_token.is_none() {
+            return Ok(roles);
+        }
+    }
+}
+
+/// List the accounts the token grants access to, following every page of the SSO `ListAccounts` API.
+///
+/// The profile is only used to name it in errors, as the accounts depend on the token alone.
+pub async fn list_accounts(
+    client: &SsoClient,
+    profile: &SsoProfile,
+    token: &CachedSsoToken,
+) -> Result<Vec<Account>> {
+    let mut accounts = Vec::new();
+    let mut next_token = None;
+
+    loop {
+        let response = client
+            .list_accounts()
+            .access_token(token.access_token.clone())
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(|e| match e {
+                SdkError::ServiceError { err, .. } if err.is_unauthorized_exception() => {
+                    Error::CacheTokenUnauthorized(profile.profile_name.clone())
+                }
+                e => proxy_failure(&e).unwrap_or_else(|| Error::SsoApi {
+                    operation: "ListAccounts",
+                    source: e.into(),
+                }),
+            })?;
+
+        for account in response.account_list.unwrap_or_default() {
+            accounts.push(Account {
+                account_id: account
+                    .account_id
+                    .ok_or(Error::IncompleteResponse("an account id"))?,
+                account_name: account.account_name,
+                email_address: account.email_address,
+            });
+        }
+
+        next_token = response.next_token.filter(|token| !token.is_empty());
+
+        if next_token.is_none() {
+            return Ok(accounts);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Detecting skew between the local clock and AWS's, which otherwise shows up as baffling expiry errors: tokens and
+//! credentials are judged expired or not by the local clock, but AWS judges them by its own.
+
+use aws_smithy_client::erase::DynConnector;
+use aws_smithy_client::hyper_ext::Adapter;
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+
+use hyper::service::Service;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+use time::OffsetDateTime;
+
+use crate::error::Result;
+
+/// How far the local clock may be from AWS's before it's worth warning about. AWS itself rejects signed requests whose
+/// time is further out than this.
+pub const SKEW_THRESHOLD: time::Duration = time::Duration::minutes(5);
+
+/// How far ahead of AWS's clock the local clock was when the last response from AWS was received.
+static OBSERVED_SKEW: Mutex<Option<time::Duration>> = Mutex::new(None);
+
+/// How far ahead of AWS's clock the local clock is, negative if it's behind, as of the last response from AWS received
+/// through a [`connector`], or `None` if there hasn't been one.
+pub fn observed_skew() -> Option<time::Duration> {
+    *OBSERVED_SKEW.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A connector for the AWS SDK clients noting the time given by the `Date` header of each response, for
+/// [`observed_skew`], and honouring the proxies configured in the environment.
+pub fn connector() -> Result<DynConnector> {
+    Ok(match crate::proxy::connector()? {
+        Some(connector) => connector,
+        None => DynConnector::new(RecordDate(
+            Adapter::builder().build(aws_smithy_client::conns::https()),
+        )),
+    })
+}
+
+/// Wraps a connector, noting the skew between the local clock and the `Date` header of each response.
+#[derive(Clone)]
+pub(crate) struct RecordDate<C>(pub(crate) C);
+
+impl<C> Service<http::Request<SdkBody>> for RecordDate<C>
+where
+    C: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+    C::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = C::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = std::result::Result<Self::Response, C::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), C::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<SdkBody>) -> Self::Future {
+        let responding = self.0.call(request);
+
+        Box::pin(async move {
+            let response = responding.await?;
+
+            if let Some(date) = response
+                .headers()
+                .get(http::header::DATE)
+                .and_then(|date| date.to_str().ok())
+                .and_then(|date| DateTime::from_str(date, Format::HttpDate).ok())
+            {
+                let skew = OffsetDateTime::now_utc().unix_timestamp() - date.secs();
+
+                *OBSERVED_SKEW.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(time::Duration::seconds(skew));
+            }
+
+            Ok(response)
+        })
+    }
+}
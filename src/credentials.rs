@@ -0,0 +1,552 @@
+//! Fetching, caching, and persisting of role credentials.
+
+use aws_sdk_sso::error::GetRoleCredentialsError;
+use aws_sdk_sso::{
+    Client as SsoClient, Config as SsoConfig, Endpoint, Region as SsoRegion, RetryConfig, SdkError,
+};
+
+use aws_smithy_async::rt::sleep::default_async_sleep;
+use aws_smithy_types::timeout::TimeoutConfig;
+
+use http::Uri;
+
+use serde::{Deserialize, Serialize};
+
+use sha1::Sha1;
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::error::{Error, Result};
+use crate::files::{aws_credentials_file, replace_private_file};
+use crate::ini::update_ini_section;
+use crate::proxy::proxy_failure;
+use crate::{CachedSsoToken, SsoProfile};
+
+/// Temporary role credentials issued by SSO for an account and role, wiped from memory when dropped, and masked when
+/// debug formatted.
+#[derive(Deserialize, Serialize, Zeroize, ZeroizeOnDrop)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+    #[serde(with = "rfc3339")]
+    #[zeroize(skip)]
+    pub expires_at: OffsetDateTime,
+}
+
+impl SsoCredentials {
+    /// The time at which the credentials expire as an RFC3339 timestamp in UTC with whole seconds, e.g.
+    /// `2030-01-01T00:00:00Z`, as expected by the AWS SDKs and CLI.
+    pub fn expiration(&self) -> Result<String> {
+        let expires_at = self.expires_at.to_offset(UtcOffset::UTC);
+
+        (expires_at - time::Duration::nanoseconds(expires_at.nanosecond().into()))
+            .format(&Rfc3339)
+            .map_err(|e| Error::Format(format!("credentials expiration: {}", e)))
+    }
+}
+
+/// Shown in place of secrets when debug formatting, so that a stray `{:?}` can never log them.
+pub(crate) const REDACTED: &str = "***";
+
+impl std::fmt::Debug for SsoCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SsoCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &REDACTED)
+            .field("session_token", &REDACTED)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// (De)serialization of an [`OffsetDateTime`] as an RFC3339 string, for use with `#[serde(with = "rfc3339")]`.
+pub(crate) mod rfc3339 {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use time::format_description::well_known::Rfc3339;
+    use time::OffsetDateTime;
+
+    pub fn serialize<S: Serializer>(
+        value: &OffsetDateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let encoded = value.format(&Rfc3339).map_err(serde::ser::Error::custom)?;
+
+        serializer.serialize_str(encoded.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OffsetDateTime, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+
+        OffsetDateTime::parse(encoded.as_str(), &Rfc3339).map_err(de::Error::custom)
+    }
+}
+
+/// Options controlling how [`fetch_sso_credentials`] fetches role credentials.
+#[derive(Clone, Debug)]
+pub struct FetchOptions {
+    /// The minimum lifetime, in seconds, that the issued credentials must have.
+    ///
+    /// The SSO `GetRoleCredentials` API does not accept a session duration: credentials are always issued for the
+    /// session duration configured on the role's permission set. When this is set, credentials which would expire
+    /// sooner are rejected with an error stating their actual lifetime.
+    pub duration_seconds: Option<u32>,
+    /// How many times to retry the request after throttling, timeouts, and server errors, with exponential backoff.
+    pub max_retries: u32,
+    /// How long each attempt at the request may take before it's abandoned, or `None` to wait indefinitely.
+    pub timeout: Option<Duration>,
+    /// The region of the SSO portal API to call instead of the one stored with the token, e.g. when the profile's
+    /// `sso_region`, from which the AWS CLI took the token's, is wrong.
+    pub region: Option<String>,
+    /// The URL of the SSO portal API to use instead of the one for the token's region.
+    pub endpoint_url: Option<String>,
+    /// Which variant of the SSO portal API endpoint for the token's region to use, unless `endpoint_url` is given.
+    pub variant: EndpointVariant,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            duration_seconds: None,
+            max_retries: 3,
+            timeout: Some(Duration::from_secs(10)),
+            region: None,
+            endpoint_url: None,
+            variant: EndpointVariant::default(),
+        }
+    }
+}
+
+/// Fetch role credentials for the profile's account and role using the cached SSO token.
+pub async fn fetch_sso_credentials(
+    profile: &SsoProfile,
+    token: &CachedSsoToken,
+    options: &FetchOptions,
+) -> Result<SsoCredentials> {
+    let client = sso_client(token, options)?;
+
+    fetch_sso_credentials_with_client(&client, profile, token, options).await
+}
+
+/// Build the SSO client used by [`fetch_sso_credentials`]: one for the token's region, or the region in the options,
+/// the variant of its endpoint in the options, or the endpoint URL in the options, with the SDK's own retries disabled.
+pub fn sso_client(token: &CachedSsoToken, options: &FetchOptions) -> Result<SsoClient> {
+    let region = sso_region(token, options);
+
+    // retries are made when fetching rather than by the sdk so that each of them can be logged
+    let mut config = SsoConfig::builder()
+        .region(SsoRegion::new(region.to_string()))
+        .retry_config(RetryConfig::disabled())
+        .timeout_config(TimeoutConfig::new().with_api_call_timeout(options.timeout));
+
+    // the client only times requests out with something to sleep on
+    config.set_sleep_impl(default_async_sleep());
+
+    let endpoint_url = match options.endpoint_url.clone() {
+        Some(endpoint_url) => Some(endpoint_url),
+        None => options.variant.endpoint_url("portal.sso", region)?,
+    };
+
+    if let Some(endpoint_url) = endpoint_url.as_deref() {
+        config = config.endpoint_resolver(Endpoint::immutable(parse_endpoint_url(endpoint_url)?));
+    }
+
+    Ok(SsoClient::from_conf_conn(
+        config.build(),
+        crate::clock::connector()?,
+    ))
+}
+
+/// The region of the SSO portal API to call with the token.
+fn sso_region<'a>(token: &'a CachedSsoToken, options: &'a FetchOptions) -> &'a str {
+    options.region.as_deref().unwrap_or(token.region.as_str())
+}
+
+/// The SSO clients built by [`sso_client`] for each region, so that fetching credentials for several profiles in the
+/// same region reuses one client, along with its pooled connections, rather than setting up a connection per profile.
+///
+/// Every client is built with the same options, so a set of clients must only be used with one [`FetchOptions`].
+#[derive(Debug, Default)]
+pub struct SsoClients {
+    clients: Mutex<HashMap<String, SsoClient>>,
+}
+
+impl SsoClients {
+    /// The client for the token's region, or the region in the options, built the first time it's needed.
+    pub fn client(&self, token: &CachedSsoToken, options: &FetchOptions) -> Result<SsoClient> {
+        let region = sso_region(token, options);
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(client) = clients.get(region) {
+            return Ok(client.clone());
+        }
+
+        let client = sso_client(token, options)?;
+
+        clients.insert(region.into(), client.clone());
+
+        Ok(client)
+    }
+}
+
+/// Which variant of the AWS endpoints to use in place of the standard ones.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EndpointVariant {
+    /// Use FIPS endpoints, which only the commercial and GovCloud (US) partitions offer.
+    pub fips: bool,
+    /// Use dualstack endpoints, reachable over IPv6 as well as IPv4, which the isolated partitions don't offer.
+    pub dualstack: bool,
+}
+
+impl EndpointVariant {
+    /// The URL of this variant of the endpoint of the service with the given prefix, e.g. `portal.sso`, in the region,
+    /// or `None` if the standard endpoint should be used.
+    pub fn endpoint_url(&self, prefix: &str, region: &str) -> Result<Option<String>> {
+        let isolated = region.starts_with("us-iso");
+        let china = region.starts_with("cn-");
+
+        let unavailable = |variant| Error::EndpointVariantUnavailable {
+            variant,
+            region: region.into(),
+        };
+
+        if self.fips && (isolated || china) {
+            return Err(unavailable("FIPS"));
+        }
+
+        if self.dualstack && isolated {
+            return Err(unavailable("dualstack"));
+        }
+
+        let host = match self.fips {
+            true => format!("{}-fips", prefix),
+            false => prefix.to_string(),
+        };
+
+        Ok(match (self.dualstack, china) {
+            (true, true) => Some(format!(
+                "https://{}.{}.api.amazonwebservices.com.cn",
+                host, region
+            )),
+            (true, false) => Some(format!("https://{}.{}.api.aws", host, region)),
+            (false, _) if self.fips => Some(format!("https://{}.{}.amazonaws.com", host, region)),
+            (false, _) => None,
+        })
+    }
+}
+
+/// Parse a URL given in place of an AWS endpoint.
+pub(crate) fn parse_endpoint_url(endpoint_url: &str) -> Result<Uri> {
+    endpoint_url
+        .parse::<Uri>()
+        .map_err(|e| Error::Parse(format!("endpoint url '{}': {}", endpoint_url, e)))
+}
+
+/// Fetch role credentials for the profile's account and role using the cached SSO token and the given client.
+///
+/// The client's own configuration decides where the request is sent; the endpoint URL in the options is ignored.
+pub async fn fetch_sso_credentials_with_client(
+    client: &SsoClient,
+    profile: &SsoProfile,
+    token: &CachedSsoToken,
+    options: &FetchOptions,
+) -> Result<SsoCredentials> {
+    let mut attempt = 0;
+
+    let response = loop {
+        let result = client
+            .get_role_credentials()
+            .account_id(profile.account_id()?)
+            .role_name(profile.role_name()?)
+            .access_token(token.access_token.clone())
+            .send()
+            .await;
+
+        match result {
+            Err(e) if attempt < options.max_retries && is_transient(&e) => {
+                let delay = retry_delay(attempt);
+
+                attempt += 1;
+
+                log::debug!(
+                    "GetRoleCredentials failed, retrying in {}ms ({} of {}): {}",
+                    delay.as_millis(),
+                    attempt,
+                    options.max_retries,
+                    e
+                );
+
+                tokio::time::sleep(delay).await;
+            }
+            result => break result,
+        }
+    };
+
+    let role_credentials = response
+        .map_err(|e| match e {
+            SdkError::ServiceError { err, .. } if err.is_unauthorized_exception() => {
+                Error::CacheTokenUnauthorized(profile.profile_name.clone())
+            }
+            SdkError::TimeoutError(_) => Error::Timeout {
+                operation: "GetRoleCredentials",
+                seconds: options.timeout.unwrap_or_default().as_secs(),
+            },
+            e => proxy_failure(&e).unwrap_or_else(|| Error::SsoApi {
+                operation: "GetRoleCredentials",
+                source: e.into(),
+            }),
+        })?
+        .role_credentials
+        .ok_or(Error::IncompleteResponse("any credentials"))?;
+
+    let credentials = SsoCredentials {
+        access_key_id: role_credentials
+            .access_key_id
+            .ok_or(Error::IncompleteResponse("an access key id"))?,
+        secret_access_key: role_credentials
+            .secret_access_key
+            .ok_or(Error::IncompleteResponse("a secret access key"))?,
+        session_token: role_credentials
+            .session_token
+            .ok_or(Error::IncompleteResponse("a session token"))?,
+        // the SSO portal API gives the expiration in milliseconds since the epoch
+        expires_at: OffsetDateTime::from_unix_timestamp_nanos(
+            i128::from(role_credentials.expiration) * 1_000_000,
+        )
+        .map_err(|e| Error::Parse(format!("role credentials expiration: {}", e)))?,
+    };
+
+    if let Some(duration_seconds) = options.duration_seconds {
+        let lifetime = credentials.expires_at - OffsetDateTime::now_utc();
+
+        if lifetime < time::Duration::seconds(duration_seconds.into()) {
+            return Err(Error::DurationUnavailable {
+                requested: duration_seconds,
+                allowed: lifetime.whole_seconds().max(0),
+            });
+        }
+    }
+
+    Ok(credentials)
+}
+
+/// Whether the request failed in a way that may well succeed if retried: throttling, timeouts, and server errors, but
+/// never errors such as an invalid token.
+fn is_transient(e: &SdkError<GetRoleCredentialsError>) -> bool {
+    match e {
+        SdkError::TimeoutError(_) => true,
+        SdkError::DispatchFailure(e) => e.is_timeout() || e.is_io(),
+        SdkError::ServiceError { err, raw } => {
+            err.is_too_many_requests_exception() || raw.http().status().is_server_error()
+        }
+        _ => false,
+    }
+}
+
+/// How long to wait before the retry following the given attempt, doubling from 200ms up to 5s.
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(200u64.saturating_mul(1 << attempt.min(5))).min(Duration::from_secs(5))
+}
+
+/// The file in the cache directory in which role credentials for the given profile's account and role are cached.
+///
+/// The file is named after the profile, with bytes other than letters, digits, `-`, `_`, and `.` percent-encoded, and
+/// the SHA1 of its start url, account, role, and name, so that editing any of them in the profile never reuses
+/// credentials cached for another account or role.
+pub fn credentials_cache_file(cache_dir: &Path, sso_profile: &SsoProfile) -> Result<PathBuf> {
+    let profile_name = sso_profile.profile_name.as_str();
+    let mut encoded = String::with_capacity(profile_name.len());
+
+    for byte in profile_name.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                encoded.push(byte.into())
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    // no start url, account id, role name, or profile name can contain a line break
+    let key = format!(
+        "{}\n{}\n{}\n{}",
+        sso_profile.sso_start_url,
+        sso_profile.account_id()?,
+        sso_profile.role_name()?,
+        profile_name
+    );
+
+    Ok(cache_dir.join(format!(
+        "aws-sso-env-{}-{}.json",
+        encoded,
+        Sha1::from(&key).hexdigest()
+    )))
+}
+
+/// Role credentials as cached by [`save_cached_credentials`], along with the account and role they were issued for.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedCredentials<C> {
+    account_id: String,
+    role_name: String,
+    credentials: C,
+}
+
+/// Load role credentials previously saved by [`save_cached_credentials`] for the profile's account and role,
+/// regardless of whether they have expired, ignoring any that can't be read.
+pub async fn load_cached_credentials(
+    path: &Path,
+    sso_profile: &SsoProfile,
+) -> Option<SsoCredentials> {
+    parse_cached_credentials(path, sso_profile)
+        .await
+        .map_err(|e| log::warn!("Unable to deserialize cached SSO credentials: {:?}", e))
+        .ok()
+        .flatten()
+}
+
+/// Read role credentials previously saved by [`save_cached_credentials`] for the profile's account and role, regardless
+/// of whether they have expired, returning `None` if none were saved for them, and an error if they can't be read.
+pub async fn parse_cached_credentials(
+    path: &Path,
+    sso_profile: &SsoProfile,
+) -> Result<Option<SsoCredentials>> {
+    let mut contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::io(path)(e)),
+    };
+
+    let cached = serde_json::from_str::<CachedCredentials<SsoCredentials>>(contents.as_str())
+        .map_err(|e| Error::Parse(format!("cached SSO credentials {}: {}", path.display(), e)));
+
+    contents.zeroize();
+
+    let cached = cached?;
+
+    match cached.account_id == sso_profile.account_id()?
+        && cached.role_name == sso_profile.role_name()?
+    {
+        true => Ok(Some(cached.credentials)),
+        false => {
+            log::debug!(
+                "Ignoring SSO credentials cached for account {} and role {}.",
+                cached.account_id,
+                cached.role_name
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Save role credentials for the profile's account and role to the given path, readable and writable only by the
+/// current user.
+pub async fn save_cached_credentials(
+    path: &Path,
+    sso_profile: &SsoProfile,
+    credentials: &SsoCredentials,
+) -> Result<()> {
+    let cached = CachedCredentials {
+        account_id: sso_profile.account_id()?.to_string(),
+        role_name: sso_profile.role_name()?.to_string(),
+        credentials,
+    };
+
+    let mut contents =
+        serde_json::to_string(&cached).map_err(|e| Error::Format(format!("credentials: {}", e)))?;
+
+    // replaced rather than overwritten, so that a file already cached with looser permissions is still left private
+    let result = replace_private_file(path, contents.as_bytes()).await;
+
+    contents.zeroize();
+
+    result
+}
+
+/// Write the given credentials into the `[profile_name]` section of the shared credentials file, returning its path.
+///
+/// All other sections and keys in the file are preserved, and the file is replaced atomically.
+pub async fn write_credentials_file(
+    credentials: &SsoCredentials,
+    profile_name: &str,
+) -> Result<PathBuf> {
+    let path = aws_credentials_file()?;
+
+    let aws_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    tokio::fs::create_dir_all(aws_dir)
+        .await
+        .map_err(Error::io(aws_dir))?;
+
+    let mut existing = match tokio::fs::read_to_string(&path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::io(&path)(e)),
+    };
+
+    let mut updated = update_ini_section(
+        existing.as_str(),
+        profile_name,
+        &[
+            ("aws_access_key_id", credentials.access_key_id.as_str()),
+            (
+                "aws_secret_access_key",
+                credentials.secret_access_key.as_str(),
+            ),
+            ("aws_session_token", credentials.session_token.as_str()),
+        ],
+    );
+
+    existing.zeroize();
+
+    let result = replace_private_file(&path, updated.as_bytes()).await;
+
+    updated.zeroize();
+
+    result.map(|_| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+
+    #[test]
+    fn zeroizes_every_secret_on_drop() {
+        assert_zeroize_on_drop::<SsoCredentials>();
+
+        let mut credentials = SsoCredentials {
+            access_key_id: "ASIAEXAMPLE".into(),
+            secret_access_key: "secret".into(),
+            session_token: "session".into(),
+            expires_at: OffsetDateTime::UNIX_EPOCH,
+        };
+
+        credentials.zeroize();
+
+        // destructured without `..`, so that adding a field fails to compile until it's covered here
+        let SsoCredentials {
+            access_key_id,
+            secret_access_key,
+            session_token,
+            expires_at: _,
+        } = &credentials;
+
+        assert!(access_key_id.is_empty());
+        assert!(secret_access_key.is_empty());
+        assert!(session_token.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! The errors returned by this crate.
+
+use std::path::PathBuf;
+
+/// An error resolving credentials, distinguishing failures that need a fresh `aws sso login` from broken configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The named profile does not exist in the AWS configuration files.
+    #[error("profile '{0}' not found")]
+    ProfileNotFound(String),
+    /// The profile lacks the named property, either directly or within its `sso-session`.
+    #[error("profile must have {0} property set")]
+    MissingProfileKey(String),
+    /// The named environment variable must be set to read a profile from the environment.
+    #[error("environment variable {0} must be set")]
+    MissingEnvVar(&'static str),
+    /// The named property of the profile has a value that can't be right, for the described reason.
+    #[error("profile '{profile}' has an invalid {key}: {reason}")]
+    InvalidProfileValue {
+        profile: String,
+        key: &'static str,
+        reason: String,
+    },
+    /// The profile refers to an `[sso-session NAME]` section which does not exist.
+    #[error("sso-session '{0}' not found")]
+    SsoSessionNotFound(String),
+    /// The cached SSO token expired at the given time, so `aws sso login` must be run again.
+    #[error("cached SSO token is expired as of {0}")]
+    CacheTokenExpired(String),
+    /// No cached SSO token exists for the named profile, so `aws sso login` must be run.
+    #[error("no cached SSO token found for profile '{0}'")]
+    CacheTokenMissing(String),
+    /// The SSO token cached in the given file couldn't be read, for the described reason, e.g. because it's malformed,
+    /// so `aws sso login` must be run again to replace it.
+    #[error("cached SSO token {} could not be read: {reason}", path.display())]
+    CacheTokenUnreadable { path: PathBuf, reason: String },
+    /// The SSO token stored in the keyring under the given key couldn't be read, for the described reason.
+    #[error("SSO token in the keyring under '{key}' could not be read: {reason}")]
+    KeyringTokenUnreadable { key: String, reason: String },
+    /// SSO rejected the cached token for the named profile as unauthorized, despite it not having expired, e.g. because
+    /// the session was revoked, so `aws sso login` must be run again.
+    #[error(
+        "cached SSO token for profile '{0}' is no longer authorized: run `aws sso login` again"
+    )]
+    CacheTokenUnauthorized(String),
+    /// The OS keyring couldn't be accessed, for the described reason.
+    #[error("unable to access the keyring: {0}")]
+    Keyring(String),
+    /// The keyring's command line tool couldn't be run, e.g. because it isn't installed, which is a matter of
+    /// configuration rather than of logging in again.
+    #[error("unable to run the keyring's tool '{program}': {reason}")]
+    KeyringUnavailable { program: String, reason: String },
+    /// A request to the named SSO API operation failed.
+    #[error("SSO {operation} request failed: {source}")]
+    SsoApi {
+        operation: &'static str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A request to the named STS API operation failed, e.g. because the SSO role may not assume the role given.
+    #[error("STS {operation} request failed: {source}")]
+    StsApi {
+        operation: &'static str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A request to the named IAM API operation failed.
+    #[error("IAM {operation} request failed: {source}")]
+    IamApi {
+        operation: &'static str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The credentials aren't permitted to call the named IAM API operation.
+    #[error("not permitted to call IAM {0}")]
+    IamAccessDenied(&'static str),
+    /// The role with the given ARN requires MFA, but no token code from the MFA device was given.
+    #[error("assuming role {0} requires MFA: pass the current code of the MFA device using --token-code")]
+    MissingTokenCode(String),
+    /// The named variant of the AWS endpoints, e.g. FIPS, was requested in the given region, whose partition has none.
+    #[error("no {variant} endpoints are available in region {region}")]
+    EndpointVariantUnavailable {
+        variant: &'static str,
+        region: String,
+    },
+    /// No connection to AWS could be made through the given proxy, for the described reason.
+    #[error("unable to connect through proxy {proxy}: {reason}")]
+    Proxy { proxy: String, reason: String },
+    /// A request to the named SSO API operation took longer than the given number of seconds, so was abandoned.
+    #[error("SSO {operation} request timed out after {seconds} seconds")]
+    Timeout {
+        operation: &'static str,
+        seconds: u64,
+    },
+    /// A response from the SSO API lacked the described value.
+    #[error("SSO response did not contain {0}")]
+    IncompleteResponse(&'static str),
+    /// The issued credentials expire sooner than the requested number of seconds.
+    #[error("requested credentials valid for {requested} seconds, but the role's session duration only allows {allowed} seconds")]
+    DurationUnavailable { requested: u32, allowed: i64 },
+    /// A value read from disk or the SSO API couldn't be parsed, for the described reason.
+    #[error("unable to parse {0}")]
+    Parse(String),
+    /// A value couldn't be formatted for output, for the described reason.
+    #[error("unable to format {0}")]
+    Format(String),
+    /// The current user's home directory couldn't be determined.
+    #[error("could not determine the current user's home directory; set HOME")]
+    HomeDirNotFound,
+    /// The given file descriptor couldn't be written to, for the described reason.
+    #[error("unable to write to file descriptor {fd}: {reason}")]
+    FileDescriptor { fd: i32, reason: String },
+    /// The file at the given path couldn't be read or written.
+    #[error("unable to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl Error {
+    /// The name of the kind of failure, which unlike the message is stable across releases, so that scripts can branch
+    /// on it, e.g. in the JSON printed by `--error-format json`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ProfileNotFound(_) => "ProfileNotFound",
+            Error::MissingProfileKey(_) => "MissingProfileKey",
+            Error::MissingEnvVar(_) => "MissingEnvVar",
+            Error::InvalidProfileValue { .. } => "InvalidProfileValue",
+            Error::SsoSessionNotFound(_) => "SsoSessionNotFound",
+            Error::CacheTokenExpired(_) => "CacheTokenExpired",
+            Error::CacheTokenMissing(_) => "CacheTokenMissing",
+            Error::CacheTokenUnreadable { .. } => "CacheTokenUnreadable",
+            Error::KeyringTokenUnreadable { .. } => "KeyringTokenUnreadable",
+            Error::CacheTokenUnauthorized(_) => "CacheTokenUnauthorized",
+            Error::Keyring(_) => "Keyring",
+            Error::KeyringUnavailable { .. } => "KeyringUnavailable",
+            Error::SsoApi { .. } => "SsoApi",
+            Error::StsApi { .. } => "StsApi",
+            Error::IamApi { .. } => "IamApi",
+            Error::IamAccessDenied(_) => "IamAccessDenied",
+            Error::MissingTokenCode(_) => "MissingTokenCode",
+            Error::EndpointVariantUnavailable { .. } => "EndpointVariantUnavailable",
+            Error::Proxy { .. } => "Proxy",
+            Error::Timeout { .. } => "Timeout",
+            Error::IncompleteResponse(_) => "IncompleteResponse",
+            Error::DurationUnavailable { .. } => "DurationUnavailable",
+            Error::Parse(_) => "Parse",
+            Error::Format(_) => "Format",
+            Error::HomeDirNotFound => "HomeDirNotFound",
+            Error::FileDescriptor { .. } => "FileDescriptor",
+            Error::Io { .. } => "Io",
+        }
+    }
+
+    /// The name of the profile the error is about, if it names one.
+    pub fn profile_name(&self) -> Option<&str> {
+        match self {
+            Error::ProfileNotFound(profile_name)
+            | Error::CacheTokenMissing(profile_name)
+            | Error::CacheTokenUnauthorized(profile_name)
+            | Error::InvalidProfileValue {
+                profile: profile_name,
+                ..
+            } => Some(profile_name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Wrap an I/O error with the path of the file being accessed.
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Error {
+        let path = path.into();
+
+        move |source| Error::Io { path, source }
+    }
+}
+
+/// The result type returned by this crate.
+pub type Result<T> = std::result::Result<T, Error>;
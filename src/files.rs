@@ -0,0 +1,209 @@
+//! Locations of the AWS configuration files, the SSO token cache, and this tool's settings.
+
+use crate::error::{Error, Result};
+
+use std::path::{Path, PathBuf};
+
+/// The path of the AWS configuration file: `AWS_CONFIG_FILE` if set, otherwise `~/.aws/config`.
+pub fn aws_config_file() -> Result<PathBuf> {
+    aws_file_from_env("AWS_CONFIG_FILE", "config")
+}
+
+/// The path of the AWS shared credentials file: `AWS_SHARED_CREDENTIALS_FILE` if set, otherwise `~/.aws/credentials`.
+pub fn aws_credentials_file() -> Result<PathBuf> {
+    aws_file_from_env("AWS_SHARED_CREDENTIALS_FILE", "credentials")
+}
+
+/// The base AWS directory, which holds the SSO token cache in `sso/cache`.
+///
+/// This is the directory containing the file named by `AWS_CONFIG_FILE` or, failing that, `AWS_SHARED_CREDENTIALS_FILE`,
+/// so that relocated configuration files find a cache alongside them. Otherwise, it is `~/.aws`.
+pub fn aws_dir() -> Result<PathBuf> {
+    for var in ["AWS_CONFIG_FILE", "AWS_SHARED_CREDENTIALS_FILE"] {
+        if let Some(parent) = path_from_env(var)?.as_deref().and_then(Path::parent) {
+            return Ok(parent.to_path_buf());
+        }
+    }
+
+    Ok(home_dir()?.join(".aws"))
+}
+
+/// The directory in which `aws sso login` caches SSO tokens, and in which role credentials are cached: `sso/cache`
+/// within the [`aws_dir`].
+pub fn sso_cache_dir() -> Result<PathBuf> {
+    Ok(aws_dir()?.join("sso").join("cache"))
+}
+
+/// The path of this tool's settings file: `AWS_SSO_ENV_CONFIG_FILE` if set, otherwise `aws-sso-env/config.toml` within
+/// `XDG_CONFIG_HOME`, which defaults to `~/.config`.
+pub fn settings_file() -> Result<PathBuf> {
+    if let Some(path) = path_from_env("AWS_SSO_ENV_CONFIG_FILE")? {
+        return Ok(path);
+    }
+
+    let config_dir = match path_from_env("XDG_CONFIG_HOME")? {
+        Some(config_dir) => config_dir,
+        None => home_dir()?.join(".config"),
+    };
+
+    Ok(config_dir.join("aws-sso-env").join("config.toml"))
+}
+
+/// Resolve the path in the given environment variable, falling back to the named file within `~/.aws`.
+fn aws_file_from_env(var: &str, default_file_name: &str) -> Result<PathBuf> {
+    match path_from_env(var)? {
+        Some(path) => Ok(path),
+        None => Ok(home_dir()?.join(".aws").join(default_file_name)),
+    }
+}
+
+/// Read a path from the given environment variable, expanding a leading `~` to the home directory like the SDK does.
+///
+/// The `~` may be followed by any of the platform's path separators, so both `~/` and `~\` expand on Windows.
+fn path_from_env(var: &str) -> Result<Option<PathBuf>> {
+    let value = match std::env::var(var) {
+        Ok(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+
+    match value.strip_prefix('~') {
+        Some("") => Ok(Some(home_dir()?)),
+        Some(rest) if rest.starts_with(std::path::is_separator) => Ok(Some(
+            home_dir()?.join(rest.trim_start_matches(std::path::is_separator)),
+        )),
+        _ => Ok(Some(PathBuf::from(value))),
+    }
+}
+
+/// The current user's home directory.
+///
+/// On Windows this follows the SDK and the AWS CLI: `HOME`, then `USERPROFILE`, then `HOMEDRIVE` and `HOMEPATH`, before
+/// falling back to the profile folder known to the system.
+fn home_dir() -> Result<PathBuf> {
+    #[cfg(windows)]
+    if let Some(home) = windows_home_dir_from_env() {
+        return Ok(home);
+    }
+
+    dirs::home_dir().ok_or(Error::HomeDirNotFound)
+}
+
+#[cfg(windows)]
+fn windows_home_dir_from_env() -> Option<PathBuf> {
+    let non_empty = |var: &str| std::env::var_os(var).filter(|value| !value.is_empty());
+
+    if let Some(home) = non_empty("HOME").or_else(|| non_empty("USERPROFILE")) {
+        return Some(PathBuf::from(home));
+    }
+
+    let mut home = PathBuf::from(non_empty("HOMEDRIVE")?);
+    home.push(non_empty("HOMEPATH")?);
+    Some(home)
+}
+
+/// Create or truncate the file at the given path, readable and writable only by the current user, with the contents.
+///
+/// On unix the file is created with mode `0600`. Windows has no equivalent mode bits, so there the file takes on the
+/// access control list of its directory which, within the user's profile, already excludes other users.
+pub async fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await
+}
+
+/// Atomically replace the file at the given path with the contents, readable and writable only by the current user.
+///
+/// The contents are written to a temporary file in the same directory which is then renamed over the original, so the
+/// file is never seen half-written, and has restrictive permissions even if it already existed with looser ones.
+pub async fn replace_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        Error::io(path)(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "not a file path",
+        ))
+    })?;
+
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = match write_private_file(&temp_path, contents).await {
+        Ok(()) => tokio::fs::rename(&temp_path, path)
+            .await
+            .map_err(Error::io(path)),
+        Err(e) => Err(Error::io(&temp_path)(e)),
+    };
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+
+    result
+}
+
+/// Check that the given file descriptor is open for writing, as it must be for [`write_to_fd`].
+#[cfg(unix)]
+pub fn check_writable_fd(fd: i32) -> Result<()> {
+    let failed = |reason: &str| Error::FileDescriptor {
+        fd,
+        reason: reason.into(),
+    };
+
+    // SAFETY: fcntl only inspects the descriptor, failing with EBADF if it isn't open
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+
+    if fd < 0 || flags == -1 {
+        return Err(failed("not an open file descriptor"));
+    }
+
+    if flags & libc::O_ACCMODE == libc::O_RDONLY {
+        return Err(failed("only open for reading"));
+    }
+
+    Ok(())
+}
+
+/// File descriptors can only be written to on unix.
+#[cfg(not(unix))]
+pub fn check_writable_fd(fd: i32) -> Result<()> {
+    Err(Error::FileDescriptor {
+        fd,
+        reason: "file descriptors are only supported on unix".into(),
+    })
+}
+
+/// Write the contents to the given file descriptor, already opened for writing by whatever started this process, e.g.
+/// `3` after `3>credentials`. The descriptor is left open.
+#[cfg(unix)]
+pub fn write_to_fd(fd: i32, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+
+    check_writable_fd(fd)?;
+
+    // SAFETY: the descriptor is open, and is never closed as the file is never dropped
+    let mut file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+
+    file.write_all(contents)
+        .and_then(|_| file.flush())
+        .map_err(|e| Error::FileDescriptor {
+            fd,
+            reason: e.to_string(),
+        })
+}
+
+/// File descriptors can only be written to on unix.
+#[cfg(not(unix))]
+pub fn write_to_fd(fd: i32, _contents: &[u8]) -> Result<()> {
+    check_writable_fd(fd)
+}
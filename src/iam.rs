@@ -0,0 +1,186 @@
+//! Looking up the alias of the account that credentials were issued for, using IAM's `ListAccountAliases`.
+//!
+//! The AWS SDK has no IAM client at the version used here, so the request is signed and sent directly.
+
+use aws_sigv4::http_request::{
+    sign, SignableBody, SignableRequest, SigningParams, SigningSettings,
+};
+
+use aws_smithy_http::body::SdkBody;
+
+use hyper::service::Service;
+
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use time::OffsetDateTime;
+
+use crate::credentials::{parse_endpoint_url, EndpointVariant};
+use crate::error::{Error, Result};
+use crate::files::write_private_file;
+use crate::proxy::proxy_failure;
+use crate::SsoCredentials;
+
+/// The body of a `ListAccountAliases` request, using IAM's query protocol.
+const LIST_ACCOUNT_ALIASES: &str = "Action=ListAccountAliases&Version=2010-05-08";
+
+/// How long a looked up alias is reused for before it's looked up again, as aliases rarely change.
+pub const ALIAS_CACHE_TTL: time::Duration = time::Duration::days(1);
+
+/// Options controlling how [`account_alias`] calls IAM.
+#[derive(Clone, Debug)]
+pub struct AliasOptions {
+    /// A region in the partition whose IAM endpoint to call, as IAM is global within each partition.
+    pub region: String,
+    /// The URL of the IAM API to use instead of the one for the partition.
+    pub endpoint_url: Option<String>,
+    /// Which variant of the IAM endpoint to use, unless `endpoint_url` is given. Only FIPS is available, in the
+    /// commercial partition, while GovCloud (US) only has FIPS endpoints anyway.
+    pub variant: EndpointVariant,
+}
+
+/// The endpoint of IAM in the partition of the given region, along with the region requests to it are signed for.
+fn iam_endpoint(region: &str, variant: EndpointVariant) -> (&'static str, &'static str) {
+    if region.starts_with("cn-") {
+        ("https://iam.cn-north-1.amazonaws.com.cn", "cn-north-1")
+    } else if region.starts_with("us-gov-") {
+        ("https://iam.us-gov.amazonaws.com", "us-gov-west-1")
+    } else if region.starts_with("us-isob-") {
+        ("https://iam.us-isob-east-1.sc2s.sgov.gov", "us-isob-east-1")
+    } else if region.starts_with("us-iso-") {
+        ("https://iam.us-iso-east-1.c2s.ic.gov", "us-iso-east-1")
+    } else if variant.fips {
+        ("https://iam-fips.amazonaws.com", "us-east-1")
+    } else {
+        ("https://iam.amazonaws.com", "us-east-1")
+    }
+}
+
+/// Look up the alias of the account the credentials were issued for, returning `None` if it has none.
+pub async fn account_alias(
+    credentials: &SsoCredentials,
+    options: &AliasOptions,
+) -> Result<Option<String>> {
+    let (default_url, signing_region) = iam_endpoint(options.region.as_str(), options.variant);
+
+    let uri = parse_endpoint_url(options.endpoint_url.as_deref().unwrap_or(default_url))?;
+
+    let failed = |source: Box<dyn std::error::Error + Send + Sync>| Error::IamApi {
+        operation: "ListAccountAliases",
+        source,
+    };
+
+    let mut request = http::Request::post(uri)
+        .header(
+            http::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded; charset=utf-8",
+        )
+        .body(LIST_ACCOUNT_ALIASES)
+        .map_err(|e| failed(e.into()))?;
+
+    let params = SigningParams::builder()
+        .access_key(credentials.access_key_id.as_str())
+        .secret_key(credentials.secret_access_key.as_str())
+        .security_token(credentials.session_token.as_str())
+        .region(signing_region)
+        .service_name("iam")
+        .time(SystemTime::now())
+        .settings(SigningSettings::default())
+        .build()
+        .map_err(|e| failed(e.into()))?;
+
+    let signable = SignableRequest::new(
+        request.method(),
+        request.uri(),
+        request.headers(),
+        SignableBody::Bytes(LIST_ACCOUNT_ALIASES.as_bytes()),
+    );
+
+    let (instructions, _) = sign(signable, &params).map_err(failed)?.into_parts();
+
+    instructions.apply_to_request(&mut request);
+
+    let mut connector = crate::clock::connector()?;
+
+    futures::future::poll_fn(|cx| connector.poll_ready(cx))
+        .await
+        .map_err(|e| failed(e.into()))?;
+
+    let response = connector
+        .call(request.map(SdkBody::from))
+        .await
+        .map_err(|e| proxy_failure(&e).unwrap_or_else(|| failed(e.into())))?;
+
+    let status = response.status();
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(failed)?;
+    let body = String::from_utf8_lossy(&body);
+
+    if status.is_success() {
+        // aliases are limited to lowercase letters, digits, and hyphens, so never need unescaping
+        return Ok(xml_element(body.as_ref(), "AccountAliases")
+            .and_then(|aliases| xml_element(aliases, "member"))
+            .map(String::from));
+    }
+
+    match xml_element(body.as_ref(), "Code") {
+        Some("AccessDenied") => Err(Error::IamAccessDenied("ListAccountAliases")),
+        code => Err(failed(
+            format!("{} {}", status, code.unwrap_or("without an error code")).into(),
+        )),
+    }
+}
+
+/// The text within the first element of the given name in the XML document.
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", name))?;
+
+    Some(xml[start..start + end].trim())
+}
+
+/// An account's alias, or lack of one, as cached by [`save_cached_alias`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedAlias {
+    account_alias: Option<String>,
+    #[serde(with = "crate::credentials::rfc3339")]
+    cached_at: OffsetDateTime,
+}
+
+/// The file in the cache directory in which the alias of the given account is cached.
+pub fn alias_cache_file(cache_dir: &Path, account_id: &str) -> PathBuf {
+    cache_dir.join(format!("aws-sso-env-alias-{}.json", account_id))
+}
+
+/// Load an account's alias previously saved by [`save_cached_alias`], returning `None` if there's none cached within
+/// [`ALIAS_CACHE_TTL`], or `Some(None)` if the account was found to have no alias.
+pub async fn load_cached_alias(path: &Path) -> Option<Option<String>> {
+    let contents = tokio::fs::read_to_string(path).await.ok()?;
+
+    let cached = serde_json::from_str::<CachedAlias>(contents.as_str())
+        .map_err(|e| log::warn!("Unable to deserialize cached account alias: {:?}", e))
+        .ok()?;
+
+    match cached.cached_at + ALIAS_CACHE_TTL > OffsetDateTime::now_utc() {
+        true => Some(cached.account_alias),
+        false => None,
+    }
+}
+
+/// Save an account's alias, or lack of one, to the given path.
+pub async fn save_cached_alias(path: &Path, account_alias: Option<&str>) -> Result<()> {
+    let contents = serde_json::to_string(&CachedAlias {
+        account_alias: account_alias.map(String::from),
+        cached_at: OffsetDateTime::now_utc(),
+    })
+    .map_err(|e| Error::Format(format!("account alias: {}", e)))?;
+
+    write_private_file(path, contents.as_bytes())
+        .await
+        .map_err(Error::io(path))
+}
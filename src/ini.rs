@@ -0,0 +1,109 @@
+//! Minimal parsing and editing of the INI files used for AWS configuration.
+
+use std::collections::HashMap;
+
+use zeroize::Zeroize;
+
+/// Set the given keys within a section of an INI document, returning the updated document.
+///
+/// Existing keys within the section are updated in place, missing keys are appended to the end of the section, and
+/// the section itself is appended to the document if it does not exist. Everything else in the document, including
+/// comments and blank lines, is left untouched.
+pub(crate) fn update_ini_section(contents: &str, section: &str, values: &[(&str, &str)]) -> String {
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+
+    match lines
+        .iter()
+        .position(|line| ini_section_name(line) == Some(section))
+    {
+        Some(start) => {
+            let mut end = lines[start + 1..]
+                .iter()
+                .position(|line| ini_section_name(line).is_some())
+                .map(|i| start + 1 + i)
+                .unwrap_or(lines.len());
+
+            // new keys go after the last key in the section, keeping trailing blank lines and comments in place
+            let mut insert_at = (start + 1..end)
+                .rev()
+                .find(|i| ini_key(lines[*i].as_str()).is_some())
+                .map(|i| i + 1)
+                .unwrap_or(start + 1);
+
+            for (key, value) in values {
+                match (start + 1..end).find(|i| ini_key(lines[*i].as_str()) == Some(key)) {
+                    Some(i) => lines[i] = format!("{} = {}", key, value),
+                    None => {
+                        lines.insert(insert_at, format!("{} = {}", key, value));
+                        insert_at += 1;
+                        end += 1;
+                    }
+                }
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+
+            lines.push(format!("[{}]", section));
+            lines.extend(
+                values
+                    .iter()
+                    .map(|(key, value)| format!("{} = {}", key, value)),
+            );
+        }
+    }
+
+    let mut output = lines.join("\n");
+    output.push('\n');
+
+    lines.zeroize();
+
+    output
+}
+
+/// Parse an INI document into a map of section names to their key-value pairs.
+///
+/// Section names are normalized so that `[ sso-session   foo ]` becomes `sso-session foo`. Keys appearing before
+/// the first section header are ignored.
+pub(crate) fn ini_sections(contents: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in contents.lines() {
+        if let Some(name) = ini_section_name(line) {
+            let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+            sections.entry(name.clone()).or_default();
+            current = Some(name);
+        } else if let (Some(section), Some(key)) = (current.as_ref(), ini_key(line)) {
+            let value = line.split_once('=').map_or("", |(_, value)| value.trim());
+
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.into(), value.into());
+        }
+    }
+
+    sections
+}
+
+/// If the line is an INI section header such as `[name]`, return the trimmed name of the section.
+pub(crate) fn ini_section_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .map(str::trim)
+}
+
+/// If the line is an INI `key = value` pair, return the trimmed key.
+pub(crate) fn ini_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+
+    if line.starts_with('#') || line.starts_with(';') {
+        return None;
+    }
+
+    line.split_once('=').map(|(key, _)| key.trim())
+}
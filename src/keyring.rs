@@ -0,0 +1,221 @@
+//! Reading and writing secrets in the OS keyring, using its command line tool: `security` on macOS, and the Secret
+//! Service's `secret-tool` elsewhere. As neither is available on Windows, the keyring isn't either.
+
+use serde::{Deserialize, Serialize};
+
+use std::io::ErrorKind;
+use std::process::Stdio;
+
+use tokio::io::AsyncWriteExt;
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::{Error, Result};
+use crate::SsoCredentials;
+
+/// The keyring service under which SSO tokens are looked up, keyed by the profile's sso-session name or start url.
+pub const TOKEN_SERVICE: &str = "aws-sso-env";
+
+/// The keyring service under which credentials are stored by [`Keyring::store_credentials`], keyed by profile name.
+pub const CREDENTIALS_SERVICE: &str = "aws-sso-env-credentials";
+
+/// The OS keyring, accessed through its command line tool.
+#[derive(Clone, Debug)]
+pub struct Keyring {
+    /// The keyring's command line tool, taking the same arguments as the platform's own.
+    pub program: String,
+}
+
+impl Default for Keyring {
+    fn default() -> Self {
+        let program = match cfg!(target_os = "macos") {
+            true => "security",
+            false => "secret-tool",
+        };
+
+        Keyring {
+            program: program.into(),
+        }
+    }
+}
+
+/// Role credentials stored in the keyring for a profile, along with what's needed to export them again without
+/// reading the profile's SSO token: who they were issued for and when that token expires.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredCredentials {
+    #[serde(flatten)]
+    pub credentials: SsoCredentials,
+    pub account_id: String,
+    pub role_name: String,
+    pub account_alias: Option<String>,
+    /// When the SSO token used to obtain the credentials expires, as an RFC3339 timestamp.
+    pub token_expires_at: String,
+}
+
+impl Keyring {
+    /// Read the secret stored under the service and account, returning `None` if there's none.
+    ///
+    /// Any other failure of the tool, e.g. because the keyring is locked or access to it was denied, is an error rather
+    /// than a missing secret, so that it isn't mistaken for a login that has expired.
+    pub async fn lookup(&self, service: &str, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let args = match cfg!(target_os = "macos") {
+            true => vec!["find-generic-password", "-s", service, "-a", account, "-w"],
+            false => vec!["lookup", "service", service, "account", account],
+        };
+
+        let output = tokio::process::Command::new(self.program.as_str())
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| self.unavailable(e))?;
+
+        let stdout = Zeroizing::new(output.stdout);
+        let empty = stdout.iter().all(u8::is_ascii_whitespace);
+
+        // security exits with 44 when nothing is stored under the account, and secret-tool with 1 and no output at all
+        let not_found = match cfg!(target_os = "macos") {
+            true => output.status.code() == Some(44),
+            false => output.status.code() == Some(1) && empty && output.stderr.is_empty(),
+        };
+
+        match (output.status.success(), not_found) {
+            (true, _) if !empty => Ok(Some(stdout)),
+            (true, _) | (false, true) => Ok(None),
+            (false, false) => Err(self.failed(output.status, &output.stderr)),
+        }
+    }
+
+    /// Store the secret under the service and account, replacing any already stored, with a label describing it.
+    ///
+    /// The secret is always written to the tool's standard input, and never passed as an argument, where any local user
+    /// could read it with `ps`. `security` prompts for it, twice, when `-w` is given last without a value, reading it
+    /// from standard input as it's run without a controlling terminal.
+    pub async fn store(
+        &self,
+        service: &str,
+        account: &str,
+        label: &str,
+        secret: &str,
+    ) -> Result<()> {
+        let mut command = tokio::process::Command::new(self.program.as_str());
+
+        match cfg!(target_os = "macos") {
+            true => command.args([
+                "add-generic-password",
+                "-U",
+                "-s",
+                service,
+                "-a",
+                account,
+                "-l",
+                label,
+                "-w",
+            ]),
+            false => command.args([
+                "store", "--label", label, "service", service, "account", account,
+            ]),
+        };
+
+        // without a controlling terminal, security prompts on standard input rather than on the terminal
+        #[cfg(target_os = "macos")]
+        unsafe {
+            command.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.unavailable(e))?;
+
+        // security asks for the secret a second time to confirm it
+        let input = Zeroizing::new(match cfg!(target_os = "macos") {
+            true => format!("{}\n{}\n", secret, secret),
+            false => secret.to_string(),
+        });
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.as_bytes())
+                .await
+                .map_err(|e| Error::Keyring(e.to_string()))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| Error::Keyring(e.to_string()))?;
+
+        match output.status.success() {
+            true => Ok(()),
+            false => Err(self.failed(output.status, &output.stderr)),
+        }
+    }
+
+    /// Store the credentials exported for the profile under [`CREDENTIALS_SERVICE`].
+    pub async fn store_credentials(
+        &self,
+        profile_name: &str,
+        stored: &StoredCredentials,
+    ) -> Result<()> {
+        let mut secret = serde_json::to_string(stored)
+            .map_err(|e| Error::Format(format!("credentials: {}", e)))?;
+
+        let result = self
+            .store(
+                CREDENTIALS_SERVICE,
+                profile_name,
+                format!("aws-sso-env credentials for profile {}", profile_name).as_str(),
+                secret.as_str(),
+            )
+            .await;
+
+        secret.zeroize();
+
+        result
+    }
+
+    /// Read the credentials stored for the profile by [`Keyring::store_credentials`], regardless of whether they have
+    /// expired, returning `None` if none are stored.
+    pub async fn load_credentials(&self, profile_name: &str) -> Result<Option<StoredCredentials>> {
+        match self.lookup(CREDENTIALS_SERVICE, profile_name).await? {
+            Some(secret) => serde_json::from_slice(secret.as_slice())
+                .map(Some)
+                .map_err(|e| {
+                    Error::Parse(format!(
+                        "credentials in the keyring for profile '{}': {}",
+                        profile_name, e
+                    ))
+                }),
+            None => Ok(None),
+        }
+    }
+
+    /// The error for the keyring's tool exiting with the given status and error output, e.g. because the keyring is
+    /// locked.
+    fn failed(&self, status: std::process::ExitStatus, stderr: &[u8]) -> Error {
+        Error::Keyring(format!(
+            "'{}' failed: {}: {}",
+            self.program,
+            status,
+            String::from_utf8_lossy(stderr).trim()
+        ))
+    }
+
+    /// The error for the keyring's tool failing to start, e.g. because it isn't installed.
+    fn unavailable(&self, e: std::io::Error) -> Error {
+        Error::KeyringUnavailable {
+            program: self.program.clone(),
+            reason: match e.kind() {
+                ErrorKind::NotFound => "not found on the PATH".into(),
+                _ => e.to_string(),
+            },
+        }
+    }
+}
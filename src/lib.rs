@@ -0,0 +1,30 @@
+//! Resolution of AWS credentials for SSO profiles.
+//!
+//! Given the name of an SSO profile in the local AWS configuration, [`get_sso_profile`] loads its configuration,
+//! [`load_cached_token`] finds the SSO token cached by `aws sso login`, and [`fetch_sso_credentials`] exchanges that
+//! token for temporary role credentials, with which [`sts::assume_role`] can go on to assume another role.
+
+pub mod accounts;
+pub mod clock;
+pub mod credentials;
+pub mod error;
+pub mod files;
+pub mod iam;
+mod ini;
+pub mod keyring;
+pub mod output;
+pub mod profile;
+pub mod proxy;
+pub mod settings;
+pub mod sts;
+pub mod token;
+
+pub use credentials::{
+    fetch_sso_credentials, fetch_sso_credentials_with_client, sso_client, FetchOptions, SsoClients,
+    SsoCredentials,
+};
+pub use error::{Error, Result};
+pub use profile::{
+    get_sso_profile, get_sso_profile_with_properties, sso_profile_from_env, SsoProfile,
+};
+pub use token::{load_cached_token, load_cached_token_from, refresh_cached_token, CachedSsoToken};
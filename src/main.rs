@@ -1,256 +1,2414 @@
 use anyhow::{anyhow, Result};
 
-use aws_sdk_sso::{Client as SsoClient, Config as SsoConfig, Region as SsoRegion};
-use aws_types::os_shim_internal::{Env, Fs};
+use aws_sso_env::accounts::{list_account_roles, list_accounts};
+use aws_sso_env::clock::{observed_skew, SKEW_THRESHOLD};
+use aws_sso_env::credentials::{
+    credentials_cache_file, load_cached_credentials, parse_cached_credentials,
+    save_cached_credentials, sso_client, write_credentials_file, EndpointVariant,
+};
+use aws_sso_env::files::{
+    check_writable_fd, replace_private_file, settings_file, sso_cache_dir, write_to_fd,
+};
+use aws_sso_env::iam::{
+    account_alias, alias_cache_file, load_cached_alias, save_cached_alias, AliasOptions,
+};
+use aws_sso_env::keyring::{Keyring, StoredCredentials, TOKEN_SERVICE};
+use aws_sso_env::output::{
+    variable_names, variable_suffix, CredentialProcessOutput, Exports, OutputFormat, Passthrough,
+    ACCOUNT_ALIAS_VARIABLE, EXPIRATION_VARIABLE, OUTPUT_VARIABLE,
+};
+use aws_sso_env::profile::{is_account_id, is_sso_profile, load_config_profiles};
+use aws_sso_env::settings::Settings;
+use aws_sso_env::sts::{assume_role, parse_role_arn, AssumeRoleOptions};
+use aws_sso_env::token::{
+    cache_files, cache_keys, FileTokenSource, KeyringTokenSource, TokenSource,
+};
+use aws_sso_env::{
+    fetch_sso_credentials, fetch_sso_credentials_with_client, get_sso_profile_with_properties,
+    refresh_cached_token, sso_profile_from_env, CachedSsoToken, Error, FetchOptions, SsoClients,
+    SsoCredentials, SsoProfile,
+};
 
-use log::LevelFilter;
+use env_logger::{Target, WriteStyle};
+
+use futures::StreamExt;
 
-use serde::Deserialize;
+use log::LevelFilter;
 
-use sha1::Sha1;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Instant;
 
+use structopt::clap::{AppSettings, ArgMatches, Shell};
 use structopt::StructOpt;
 
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
-use tokio;
-
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Extract and export AWS environment variables for a specified SSO profile.
 #[derive(Debug, StructOpt)]
+#[structopt(global_settings = &[AppSettings::DisableVersion])]
 pub struct Args {
-    /// The name of an SSO profile in your local AWS configuration file(s).
-    pub profile_name: String,
+    /// The names of SSO profiles in your local AWS configuration file(s). Defaults to the value of `AWS_SSO_ENV_PROFILE`,
+    /// or else `AWS_PROFILE`. When several are given, the name of every variable is suffixed with its profile's, e.g.
+    /// `AWS_ACCESS_KEY_ID_PROD`.
+    #[structopt(name = "profile-name")]
+    pub profile_names: Vec<String>,
+    /// The name of an SSO profile, as an alternative to the positional argument, as accepted by the AWS CLI.
+    #[structopt(short, long, value_name = "name", conflicts_with = "profile-name")]
+    pub profile: Option<String>,
+    /// The syntax in which to print the credentials. The default `bash` format exports the same variables in the same
+    /// order as `env`, which matches `aws configure export-credentials`, adding comments, the region, and the rest.
+    #[structopt(long, default_value = "bash", possible_values = OutputFormat::VARIANTS)]
+    pub format: OutputFormat,
+    /// Print the credentials as JSON, along with the account, role, and profile they were issued for. Equivalent to
+    /// `--format json`.
+    #[structopt(long, conflicts_with = "format")]
+    pub json: bool,
+    /// Print credentials in the JSON format expected by the AWS `credential_process` setting, ignoring `--format`.
+    #[structopt(long)]
+    pub credential_process: bool,
+    /// Print only the names of the variables that would be exported, one per line, without fetching any credentials.
+    #[structopt(long, conflicts_with_all = &["credential-process", "write-profile", "output"])]
+    pub print_env_names: bool,
+    /// Print only the whole number of seconds until the profile's cached SSO token or its credentials expire, e.g. for
+    /// a shell prompt. The credentials are obtained as when exporting them, while the token is only looked up.
+    #[structopt(
+        long,
+        value_name = "of",
+        possible_values = ExpiresIn::VARIANTS,
+        conflicts_with_all = &["credential-process", "write-profile", "print-env-names", "output"]
+    )]
+    pub expires_in: Option<ExpiresIn>,
+    /// With --expires-in, print how long ago an expired token expired as a negative number, rather than 0.
+    #[structopt(long, requires = "expires-in")]
+    pub allow_negative: bool,
+    /// Check that each profile is complete and has a valid cached SSO token, and print which account and role
+    /// credentials would be fetched for, without calling AWS at all. Expired tokens aren't refreshed.
+    #[structopt(
+        long,
+        conflicts_with_all = &["credential-process", "write-profile", "print-env-names", "output", "expires-in", "login"]
+    )]
+    pub dry_run: bool,
+    /// Write the credentials to the given file, readable only by the current user, instead of standard output.
+    #[structopt(
+        long,
+        value_name = "path",
+        parse(from_os_str),
+        conflicts_with_all = &["credential-process", "write-profile"]
+    )]
+    pub output: Option<PathBuf>,
+    /// Write the credentials to the given file descriptor, already open for writing, instead of standard output, e.g.
+    /// `--output-fd 3` with `3>credentials`, keeping standard output free for other data. Unix only.
+    #[structopt(
+        long,
+        value_name = "n",
+        conflicts_with_all = &["output", "credential-process", "write-profile"]
+    )]
+    pub output_fd: Option<i32>,
+    /// Write the credentials into the given profile within the shared credentials file instead of printing them.
+    #[structopt(long, value_name = "name")]
+    pub write_profile: Option<String>,
+    /// Keep running, writing fresh credentials to the file given by `--output` or `--write-profile` shortly before
+    /// they expire, until interrupted. Credentials are refreshed `--expiry-margin` seconds before they expire.
+    #[structopt(
+        long,
+        conflicts_with_all = &["credential-process", "print-env-names", "expires-in", "dry-run"]
+    )]
+    pub watch: bool,
+    /// The minimum lifetime, in seconds, that the issued credentials must have, between 900 and 43200. If omitted,
+    /// credentials are accepted with whatever session duration is configured on the role's permission set.
+    #[structopt(long, value_name = "seconds", parse(try_from_str = parse_duration_seconds))]
+    pub duration_seconds: Option<u32>,
+    /// The minimum lifetime that the issued credentials must have, as `--duration-seconds` but human readable, e.g.
+    /// `1h`, `45m`, or `1h 30m`.
+    #[structopt(
+        long,
+        value_name = "duration",
+        conflicts_with = "duration-seconds",
+        parse(try_from_str = parse_duration)
+    )]
+    pub duration: Option<u32>,
+    /// Run `aws sso login` for the profile if its cached SSO token is missing or expired. The AWS CLI executable can
+    /// be overridden using the `AWS_SSO_ENV_AWS_CLI` environment variable.
+    #[structopt(long)]
+    pub login: bool,
+    /// Fetch fresh credentials from SSO even if previously fetched credentials are cached and still valid.
+    #[structopt(long)]
+    pub refresh: bool,
+    /// The directory holding the SSO tokens cached by `aws sso login`, in which role credentials are cached too.
+    /// Defaults to `sso/cache` within the AWS configuration directory.
+    #[structopt(long, value_name = "path", parse(from_os_str))]
+    pub cache_dir: Option<PathBuf>,
+    /// Another directory in which to look for SSO tokens, such as one written to by another SSO tool, searched after
+    /// the cache directory. May be given several times, in which case they are searched in the order given.
+    #[structopt(long, value_name = "path", parse(from_os_str), number_of_values = 1)]
+    pub token_cache_dir: Vec<PathBuf>,
+    /// Where to read the SSO token from: the files cached by `aws sso login`, or the OS keyring, under the service
+    /// `aws-sso-env` and the profile's sso-session name or start url. The keyring's command line tool can be overridden
+    /// using the `AWS_SSO_ENV_KEYRING_COMMAND` environment variable.
+    #[structopt(long, value_name = "source", default_value = "file", possible_values = TokenBackend::VARIANTS)]
+    pub token_source: TokenBackend,
+    /// Neither reuse nor save credentials in this tool's own credential cache, always fetching them from SSO. The SSO
+    /// token cached by `aws sso login` is used as usual.
+    #[structopt(long)]
+    pub no_cache: bool,
+    /// Print where the SSO token is looked for to standard error: the cache directory, the file each cache key hashes
+    /// to, whether they exist, and when the token found expires. The token itself is never printed.
+    #[structopt(long)]
+    pub debug_cache: bool,
+    /// Print how long each phase of obtaining credentials took to standard error, as `phase=<phase> profile=<name>
+    /// ms=<milliseconds>` lines: `profile` loading, finding the SSO `token`, reading the `credentials-cache`, calling
+    /// `sso`, `sts`, and `iam` when they're called, and the `total`.
+    #[structopt(long)]
+    pub timings: bool,
+    /// Read the SSO profile from the `AWS_SSO_START_URL`, `AWS_SSO_REGION`, `AWS_SSO_ACCOUNT_ID`, `AWS_SSO_ROLE_NAME`,
+    /// and `AWS_REGION` environment variables instead of the AWS configuration files, e.g. in a container without any.
+    #[structopt(long)]
+    pub from_env: bool,
+    /// Never contact AWS, only reusing credentials from this tool's own credential cache and failing if none are valid.
+    /// Expired SSO tokens aren't refreshed either.
+    #[structopt(long, conflicts_with_all = &["login", "refresh", "no-cache"])]
+    pub offline: bool,
+    /// Treat SSO tokens and cached credentials as expired this many seconds before they actually expire, so that
+    /// credentials aren't handed out just before they stop working.
+    #[structopt(long, value_name = "seconds", default_value = "60")]
+    pub expiry_margin: u32,
+    /// Warn when the credentials obtained expire within this many minutes, e.g. before starting a long-running task.
+    /// Pass 0 to never warn.
+    #[structopt(long, value_name = "minutes", default_value = "15")]
+    pub warn_before: u32,
+    /// The account to fetch credentials for instead of the profile's `sso_account_id`, using the profile's cached SSO
+    /// token.
+    #[structopt(long, value_name = "id", parse(try_from_str = parse_account_id))]
+    pub account_id: Option<String>,
+    /// The role to fetch credentials for instead of the profile's `sso_role_name`, using the profile's cached SSO
+    /// token.
+    #[structopt(long, value_name = "name")]
+    pub role_name: Option<String>,
+    /// After obtaining credentials from SSO, use them to assume the role with the given ARN using STS, and export the
+    /// assumed role's credentials instead.
+    #[structopt(long, value_name = "arn", parse(try_from_str = parse_role_arn_arg), conflicts_with = "offline")]
+    pub assume_role: Option<String>,
+    /// The external id required by the trust policy of the role given by `--assume-role`.
+    #[structopt(long, value_name = "id", requires = "assume-role")]
+    pub external_id: Option<String>,
+    /// The session name of the role given by `--assume-role`, as seen in CloudTrail. Defaults to `aws-sso-env`.
+    #[structopt(long, value_name = "name", requires = "assume-role")]
+    pub role_session_name: Option<String>,
+    /// The serial number or ARN of the MFA device required by the trust policy of the role given by `--assume-role`.
+    #[structopt(long, value_name = "serial", requires = "assume-role")]
+    pub mfa_serial: Option<String>,
+    /// The current 6-digit code of the MFA device given by `--mfa-serial`.
+    #[structopt(long, value_name = "code", parse(try_from_str = parse_token_code), requires = "mfa-serial")]
+    pub token_code: Option<String>,
+    /// The region exported as `AWS_DEFAULT_REGION` and `AWS_REGION` alongside the credentials, overriding the
+    /// profile's `region`.
+    #[structopt(long, value_name = "region")]
+    pub region: Option<String>,
+    /// The region of the SSO portal to call, overriding the profile's `sso_region` and the region stored with the
+    /// cached SSO token, e.g. to debug endpoint issues.
+    #[structopt(long, value_name = "region")]
+    pub sso_region: Option<String>,
+    /// Don't export `AWS_DEFAULT_REGION` and `AWS_REGION`, e.g. when the region is already set elsewhere.
+    #[structopt(long, conflicts_with = "region")]
+    pub no_region: bool,
+    /// Export the value of a property of the profile as the given variable, alongside the credentials, e.g.
+    /// `cli_pager=AWS_PAGER`. May be given several times. The profile's `output` is always exported as
+    /// `AWS_DEFAULT_OUTPUT`.
+    #[structopt(long, value_name = "key=variable", number_of_values = 1)]
+    pub passthrough_key: Vec<Passthrough>,
+    /// The name of the variable holding when the credentials expire. May be given several times to export the
+    /// expiration under each name, e.g. also as the legacy `AWS_SESSION_EXPIRATION`.
+    #[structopt(
+        long,
+        value_name = "name",
+        default_value = EXPIRATION_VARIABLE,
+        number_of_values = 1,
+        parse(try_from_str = parse_variable_name)
+    )]
+    pub expiration_var_name: Vec<String>,
+    /// Prepend the prefix and an underscore to the name of every exported variable, e.g. `MYAPP` to export
+    /// `MYAPP_AWS_ACCESS_KEY_ID`, for tools wrapping several sets of credentials. JSON output is unaffected.
+    #[structopt(long, value_name = "prefix", parse(try_from_str = parse_env_prefix))]
+    pub env_prefix: Option<String>,
+    /// Don't precede the credentials with comments noting the role's ARN and when the SSO token expires.
+    #[structopt(long)]
+    pub no_comments: bool,
+    /// Look up the alias of the account using IAM's `ListAccountAliases`, noting it in a comment and exporting it as
+    /// `AWS_ACCOUNT_ALIAS`. Aliases are cached for a day, and skipped if the credentials may not list them.
+    #[structopt(long)]
+    pub print_account_alias: bool,
+    /// Fail rather than quietly leave out or work around anything optional: a profile without a region, unless
+    /// --no-region is given, a property given by --passthrough-key that the profile doesn't set, an account alias that
+    /// can't be looked up, and cached credentials that can't be read.
+    #[structopt(long)]
+    pub strict: bool,
+    /// How many times to retry fetching credentials after throttling, timeouts, and server errors from SSO, waiting
+    /// exponentially longer between each attempt.
+    #[structopt(long, value_name = "count", default_value = "3")]
+    pub max_retries: u32,
+    /// How many seconds each request to SSO may take before it's abandoned and, if retries remain, retried. Pass 0 to
+    /// wait indefinitely.
+    #[structopt(long, value_name = "seconds", default_value = "10")]
+    pub timeout: u64,
+    /// How many profiles to fetch credentials for at once when exporting several, to avoid being throttled by SSO.
+    /// Profiles are fetched one at a time with `--login`, so that only one login prompt is shown at once.
+    #[structopt(long, value_name = "count", default_value = "4", parse(try_from_str = parse_max_concurrency))]
+    pub max_concurrency: usize,
+    /// The URL of the SSO portal API to use instead of the one for the token's region, e.g. in an isolated region.
+    #[structopt(long, alias = "endpoint-url", value_name = "url", env = SSO_ENDPOINT_URL_ENV_VAR)]
+    pub sso_endpoint_url: Option<String>,
+    /// Use the FIPS endpoints of SSO, and of SSO OIDC and STS when refreshing tokens and assuming roles, for the
+    /// region. Only available in the commercial and GovCloud (US) partitions.
+    #[structopt(long)]
+    pub fips: bool,
+    /// Use the dualstack endpoints, reachable over IPv6 as well as IPv4, of SSO, and of SSO OIDC and STS when
+    /// refreshing tokens and assuming roles. Also enabled by setting `AWS_USE_DUALSTACK_ENDPOINT` to `true`.
+    #[structopt(long)]
+    pub dualstack: bool,
+    /// The URL of the SSO OIDC API to refresh tokens with instead of the one for the token's region, for testing.
+    #[structopt(long, value_name = "url", hidden = true)]
+    pub oidc_endpoint_url: Option<String>,
+    /// The URL of the STS API to assume roles with instead of the one for the region, for testing.
+    #[structopt(long, value_name = "url", hidden = true)]
+    pub sts_endpoint_url: Option<String>,
+    /// The URL of the IAM API to look up account aliases with instead of the one for the partition, for testing.
+    #[structopt(long, value_name = "url", hidden = true)]
+    pub iam_endpoint_url: Option<String>,
+    /// Only log errors, so that nothing but the credentials is printed.
+    #[structopt(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Log in more detail. Pass twice to include logs from the AWS SDK and HTTP libraries.
+    #[structopt(short, long, parse(from_occurrences))]
+    pub verbose: u8,
+    /// The format of the logs written to standard error: human-readable text, or a JSON object per line.
+    #[structopt(long, value_name = "format", default_value = "text", possible_values = LogFormat::VARIANTS)]
+    pub log_format: LogFormat,
+    /// The format of the error written to standard error on failure: human-readable text, or a JSON object with
+    /// `error`, `message`, and `profile` keys, where `error` is a stable code naming the kind of failure.
+    #[structopt(long, value_name = "format", default_value = "text", possible_values = ErrorFormat::VARIANTS)]
+    pub error_format: ErrorFormat,
+    /// When to color the logs written to standard error: only when it's a terminal, always, or never. Follows
+    /// `RUST_LOG_STYLE` when not given, and is otherwise `auto`.
+    #[structopt(long, value_name = "when", possible_values = Color::VARIANTS)]
+    pub color: Option<Color>,
+    /// Never color the logs written to standard error, as with `--color never`.
+    #[structopt(long, conflicts_with = "color")]
+    pub no_color: bool,
+    /// Print the version, along with the commit and time it was built from. With --verbose, print the versions of the
+    /// AWS SDK crates too.
+    #[structopt(short = "V", long)]
+    pub version: bool,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
 }
 
-/// Representation of an SSO profile's configuration within `~/.aws/config` or `~/.aws/credentials`.
-///
-/// This struct contains all the necessary fields to facilitate single-sign-on for an AWS account with a role.
-#[derive(Debug)]
-pub struct SsoProfile {
-    pub profile_name: String,
-    pub region: String,
-    pub sso_account_id: String,
-    pub sso_region: String,
-    pub sso_role_name: String,
-    pub sso_start_url: String,
+/// Commands other than the default of exporting credentials for a profile.
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// List the profiles in your AWS configuration file, noting which of them are SSO profiles.
+    ListProfiles,
+    /// Check that a profile is well-formed and has a valid cached SSO token, without fetching credentials.
+    Check {
+        /// The name of an SSO profile in your local AWS configuration file(s). Defaults to the value of
+        /// `AWS_SSO_ENV_PROFILE`, or else `AWS_PROFILE`.
+        profile_name: Option<String>,
+        /// Print and log nothing at all, only exiting with 0 if the cached SSO token is valid, 10 if it has expired,
+        /// and 11 if it is missing, e.g. for use in a shell prompt.
+        #[structopt(long)]
+        status_only: bool,
+    },
+    /// List the accounts you have access to through SSO, using the profile's cached SSO token.
+    Accounts {
+        /// The name of an SSO profile in your local AWS configuration file(s). Defaults to the value of
+        /// `AWS_SSO_ENV_PROFILE`, or else `AWS_PROFILE`.
+        profile_name: Option<String>,
+        /// Print the accounts as a JSON array of objects with `accountId`, `accountName`, and `emailAddress` keys.
+        #[structopt(long)]
+        json: bool,
+    },
+    /// List the roles you may use in the profile's account, using its cached SSO token.
+    Roles {
+        /// The name of an SSO profile in your local AWS configuration file(s). Defaults to the value of
+        /// `AWS_SSO_ENV_PROFILE`, or else `AWS_PROFILE`.
+        profile_name: Option<String>,
+        /// Print the roles as a JSON array of objects with `accountId` and `roleName` keys.
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Print the commands that clear every variable exporting credentials can set, in the syntax given by `--format`,
+    /// e.g. `eval "$(aws-sso-env unset)"`.
+    Unset {
+        /// The names of the profiles whose suffixed variables to clear, as when exporting several profiles. If fewer
+        /// than two are given, the unsuffixed variables are cleared.
+        #[structopt(name = "profile-name")]
+        profile_names: Vec<String>,
+    },
+    /// Fetch the profile's credentials and store them in the OS keyring rather than printing them, for other tools to
+    /// read from there, or to export later with `load`.
+    Store {
+        /// The name of an SSO profile in your local AWS configuration file(s). Defaults to the value of
+        /// `AWS_SSO_ENV_PROFILE`, or else `AWS_PROFILE`.
+        profile_name: Option<String>,
+    },
+    /// Print the credentials stored in the OS keyring for the profile by `store`, in the syntax given by `--format`,
+    /// without calling SSO.
+    Load {
+        /// The name of an SSO profile in your local AWS configuration file(s). Defaults to the value of
+        /// `AWS_SSO_ENV_PROFILE`, or else `AWS_PROFILE`.
+        profile_name: Option<String>,
+    },
+    /// Print a completion script for the given shell to standard output.
+    Completions {
+        /// The shell for which to generate completions.
+        #[structopt(possible_values = &Shell::variants())]
+        shell: Shell,
+    },
+}
+
+impl Args {
+    /// The syntax in which to print the credentials, taking `--json` into account.
+    pub fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        }
+    }
+
+    /// The profiles to use: the positional arguments or `--profile` if given, otherwise the one named in the
+    /// environment, as for [`Args::resolve_profile_name`].
+    pub fn profile_names(&self) -> Result<Vec<String>> {
+        match self.profile_names.is_empty() {
+            true => Ok(vec![self.resolve_profile_name(None)?]),
+            false => Ok(self.profile_names.clone()),
+        }
+    }
+
+    /// The name of the single profile the command is for, if it has one, to report alongside any error.
+    pub fn failed_profile_name(&self) -> Option<String> {
+        match &self.command {
+            Some(Command::Check { profile_name, .. })
+            | Some(Command::Accounts { profile_name, .. })
+            | Some(Command::Roles { profile_name, .. })
+            | Some(Command::Store { profile_name })
+            | Some(Command::Load { profile_name }) => {
+                self.resolve_profile_name(profile_name.as_deref()).ok()
+            }
+            Some(Command::ListProfiles)
+            | Some(Command::Unset { .. })
+            | Some(Command::Completions { .. }) => None,
+            None => match self.profile_names.len() {
+                0 | 1 => self.profile_names().ok()?.pop(),
+                _ => None,
+            },
+        }
+    }
+
+    /// Use the given profile name if present, otherwise `--profile`, otherwise the `AWS_SSO_ENV_PROFILE` environment
+    /// variable, so that wrappers can bake a profile in, and otherwise `AWS_PROFILE`. With `--from-env`, the profile
+    /// needn't be named at all.
+    pub fn resolve_profile_name(&self, profile_name: Option<&str>) -> Result<String> {
+        let from_env = [PROFILE_ENV_VAR, "AWS_PROFILE"].iter().find_map(|name| {
+            std::env::var(name)
+                .ok()
+                .filter(|profile_name| !profile_name.is_empty())
+        });
+
+        match profile_name.or(self.profile.as_deref()) {
+            Some(profile_name) => Ok(profile_name.into()),
+            None => from_env
+                .or_else(|| self.from_env.then(|| ENV_PROFILE_NAME.into()))
+                .ok_or(anyhow!(
+                    "no profile name given and neither the {} nor the AWS_PROFILE environment variable is set",
+                    PROFILE_ENV_VAR
+                )),
+        }
+    }
+
+    /// Load the named SSO profile from the AWS configuration files, or from the environment with `--from-env`, with its
+    /// `sso_region` overridden by `--sso-region`.
+    pub async fn sso_profile(&self, profile_name: &str) -> Result<SsoProfile> {
+        let mut sso_profile = match self.from_env {
+            true => sso_profile_from_env(profile_name)?,
+            false => {
+                let keys: Vec<&str> = self
+                    .passthrough_key
+                    .iter()
+                    .map(|passthrough| passthrough.key.as_str())
+                    .collect();
+
+                get_sso_profile_with_properties(profile_name, keys.as_slice()).await?
+            }
+        };
+
+        if let Some(sso_region) = self.sso_region.as_ref() {
+            sso_profile.sso_region = sso_region.clone();
+        }
+
+        Ok(sso_profile)
+    }
+
+    /// The region to export with the credentials for the given profile, if any.
+    pub fn region<'a>(&'a self, sso_profile: &'a SsoProfile) -> Option<&'a str> {
+        // an empty region is as good as none at all
+        self.region
+            .as_deref()
+            .or(sso_profile.region.as_deref())
+            .filter(|region| !self.no_region && !region.is_empty())
+    }
+
+    /// With `--strict`, fail if the profile lacks anything that would otherwise be quietly left out of the exports.
+    pub fn check_strict(&self, profile_name: &str, sso_profile: &SsoProfile) -> Result<()> {
+        if !self.strict {
+            return Ok(());
+        }
+
+        if !self.no_region && self.region(sso_profile).is_none() {
+            return Err(anyhow!(
+                "--strict: profile '{}' has no region: set its region, or pass --region or --no-region",
+                profile_name
+            ));
+        }
+
+        if let Some(passthrough) = self
+            .passthrough_key
+            .iter()
+            .find(|passthrough| !sso_profile.properties.contains_key(&passthrough.key))
+        {
+            return Err(anyhow!(
+                "--strict: profile '{}' does not set {}, given by --passthrough-key",
+                profile_name,
+                passthrough.key
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The directory holding the cached SSO tokens, and in which role credentials are cached.
+    pub fn cache_dir(&self) -> Result<PathBuf> {
+        match self.cache_dir.as_ref() {
+            Some(cache_dir) => Ok(cache_dir.clone()),
+            None => Ok(sso_cache_dir()?),
+        }
+    }
+
+    /// The directories in which to look for cached SSO tokens, in order: the cache directory, then each given by
+    /// `--token-cache-dir`.
+    pub fn token_cache_dirs(&self) -> Result<Vec<PathBuf>> {
+        let mut cache_dirs = vec![self.cache_dir()?];
+
+        cache_dirs.extend(self.token_cache_dir.iter().cloned());
+
+        Ok(cache_dirs)
+    }
+
+    /// Where to read SSO tokens from, as given by `--token-source`.
+    pub fn token_source(&self) -> Result<Box<dyn TokenSource + Send + Sync>> {
+        Ok(match self.token_source {
+            TokenBackend::File => Box::new(FileTokenSource {
+                cache_dirs: self.token_cache_dirs()?,
+                expiry_cutoff: self.expiry_cutoff(),
+            }),
+            TokenBackend::Keyring => Box::new(KeyringTokenSource { keyring: keyring() }),
+        })
+    }
+
+    /// Find the profile's cached SSO token, the first in the token cache directories that hasn't expired if any.
+    pub async fn cached_token(&self, sso_profile: &SsoProfile) -> Result<Option<CachedSsoToken>> {
+        Ok(self.token_source()?.load(sso_profile).await?)
+    }
+
+    /// Why no token could be found for the profile, having found none: because one in the cache directories or the
+    /// keyring can't be read, or because there isn't any.
+    pub async fn missing_token(
+        &self,
+        profile_name: &str,
+        sso_profile: &SsoProfile,
+    ) -> Result<Error> {
+        Ok(match self.token_source()?.unreadable(sso_profile).await {
+            Some(e) => e,
+            None => Error::CacheTokenMissing(profile_name.into()),
+        })
+    }
+
+    /// Print how long the phase of obtaining credentials for the profile took since it started, for `--timings`.
+    pub fn timing(&self, phase: &str, profile_name: &str, started: Instant) {
+        if self.timings {
+            eprintln!(
+                "phase={} profile={} ms={}",
+                phase,
+                profile_name,
+                started.elapsed().as_millis()
+            );
+        }
+    }
+
+    /// Take the defaults for any flags not given on the command line from the settings file, if there is one.
+    pub async fn apply_settings(&mut self, matches: &ArgMatches<'_>) -> Result<()> {
+        let settings = Settings::load(settings_file()?.as_path()).await?;
+
+        let unset = |name: &str| matches.occurrences_of(name) == 0;
+
+        if let Some(format) = settings.format.as_deref() {
+            if unset("format") && !self.json {
+                self.format = format.parse()?;
+            }
+        }
+
+        if let Some(log_format) = settings.log_format.as_deref() {
+            if unset("log-format") {
+                self.log_format = log_format.parse()?;
+            }
+        }
+
+        if let Some(error_format) = settings.error_format.as_deref() {
+            if unset("error-format") {
+                self.error_format = error_format.parse()?;
+            }
+        }
+
+        if let Some(color) = settings.color.as_deref() {
+            if unset("color") && !self.no_color {
+                self.color = Some(color.parse()?);
+            }
+        }
+
+        if let Some(duration) = self.duration {
+            self.duration_seconds = Some(duration);
+        }
+
+        if unset("duration-seconds") && unset("duration") {
+            if let Some(duration_seconds) = settings.duration_seconds {
+                self.duration_seconds = Some(parse_duration_seconds(
+                    duration_seconds.to_string().as_str(),
+                )?);
+            } else if let Some(duration) = settings.duration.as_deref() {
+                self.duration_seconds = Some(parse_duration(duration)?);
+            }
+        }
+
+        if let Some(max_concurrency) = settings.max_concurrency {
+            if unset("max-concurrency") {
+                self.max_concurrency = parse_max_concurrency(max_concurrency.to_string().as_str())?;
+            }
+        }
+
+        if unset("expiry-margin") {
+            self.expiry_margin = settings.expiry_margin.unwrap_or(self.expiry_margin);
+        }
+
+        if unset("warn-before") {
+            self.warn_before = settings.warn_before.unwrap_or(self.warn_before);
+        }
+
+        if unset("max-retries") {
+            self.max_retries = settings.max_retries.unwrap_or(self.max_retries);
+        }
+
+        if unset("timeout") {
+            self.timeout = settings.timeout.unwrap_or(self.timeout);
+        }
+
+        if unset("cache-dir") {
+            self.cache_dir = settings.cache_dir;
+        }
+
+        if unset("token-cache-dir") {
+            self.token_cache_dir = settings.token_cache_dir;
+        }
+
+        if let Some(token_source) = settings.token_source.as_deref() {
+            if unset("token-source") {
+                self.token_source = token_source.parse()?;
+            }
+        }
+
+        if unset("passthrough-key") {
+            self.passthrough_key = settings
+                .passthrough_key
+                .iter()
+                .map(|passthrough| passthrough.parse())
+                .collect::<std::result::Result<_, _>>()?;
+        }
+
+        if !settings.expiration_var_name.is_empty() && unset("expiration-var-name") {
+            self.expiration_var_name = settings
+                .expiration_var_name
+                .iter()
+                .map(|name| parse_variable_name(name.as_str()))
+                .collect::<Result<_>>()?;
+        }
+
+        if let Some(env_prefix) = settings.env_prefix.as_deref() {
+            if unset("env-prefix") {
+                self.env_prefix = Some(parse_env_prefix(env_prefix)?);
+            }
+        }
+
+        // flags can only be turned on, and never when they would conflict with those given
+        self.login |= settings.login && !self.offline;
+        self.no_cache |= settings.no_cache && !self.offline;
+        self.no_region |= settings.no_region && self.region.is_none();
+        self.no_comments |= settings.no_comments;
+        self.print_account_alias |= settings.print_account_alias;
+        self.strict |= settings.strict;
+        self.fips |= settings.fips;
+        self.dualstack |= settings.dualstack;
+        self.quiet |= settings.quiet && self.verbose == 0;
+
+        Ok(())
+    }
+
+    /// How to fetch role credentials from SSO.
+    pub fn fetch_options(&self) -> FetchOptions {
+        FetchOptions {
+            duration_seconds: self.duration_seconds,
+            max_retries: self.max_retries,
+            timeout: match self.timeout {
+                0 => None,
+                seconds => Some(std::time::Duration::from_secs(seconds)),
+            },
+            region: self.sso_region.clone(),
+            endpoint_url: self.sso_endpoint_url.clone(),
+            variant: self.endpoint_variant(),
+        }
+    }
+
+    /// Which variant of the AWS endpoints to use, following `AWS_USE_DUALSTACK_ENDPOINT` as the AWS SDKs do.
+    pub fn endpoint_variant(&self) -> EndpointVariant {
+        let dualstack_from_env = std::env::var(DUALSTACK_ENV_VAR)
+            .map(|value| value.trim().eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        EndpointVariant {
+            fips: self.fips,
+            dualstack: self.dualstack || dualstack_from_env,
+        }
+    }
+
+    /// Tokens and credentials expiring before this time are considered to be expired already.
+    pub fn expiry_cutoff(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc() + time::Duration::seconds(self.expiry_margin.into())
+    }
+}
+
+/// The name given to the profile read from the environment with `--from-env` when no name is given.
+const ENV_PROFILE_NAME: &str = "env";
+
+/// The environment variable naming the profile to use when none is given, taking precedence over `AWS_PROFILE`.
+pub const PROFILE_ENV_VAR: &str = "AWS_SSO_ENV_PROFILE";
+
+/// The environment variable naming the AWS CLI executable to run for `--login`, defaulting to `aws`.
+pub const AWS_CLI_ENV_VAR: &str = "AWS_SSO_ENV_AWS_CLI";
+
+/// The environment variable naming the keyring's command line tool for `--token-source keyring`, defaulting to
+/// `security` on macOS and `secret-tool` elsewhere.
+pub const KEYRING_COMMAND_ENV_VAR: &str = "AWS_SSO_ENV_KEYRING_COMMAND";
+
+/// The environment variable holding the URL of the SSO portal API, as an alternative to `--sso-endpoint-url`.
+const SSO_ENDPOINT_URL_ENV_VAR: &str = "AWS_SSO_ENV_SSO_ENDPOINT_URL";
+
+/// The environment variable with which the AWS SDKs enable dualstack endpoints, as an alternative to `--dualstack`.
+const DUALSTACK_ENV_VAR: &str = "AWS_USE_DUALSTACK_ENDPOINT";
+
+/// The session name of roles assumed with `--assume-role` when `--role-session-name` isn't given.
+const ROLE_SESSION_NAME: &str = "aws-sso-env";
+
+/// The shortest and longest session durations that AWS allows for role credentials.
+const DURATION_SECONDS_RANGE: std::ops::RangeInclusive<u32> = 900..=43200;
+
+fn parse_account_id(s: &str) -> Result<String> {
+    match is_account_id(s) {
+        true => Ok(s.into()),
+        false => Err(anyhow!("'{}' is not a 12-digit account id", s)),
+    }
+}
+
+fn parse_role_arn_arg(s: &str) -> Result<String> {
+    parse_role_arn(s)?;
+
+    Ok(s.into())
+}
+
+fn parse_token_code(s: &str) -> Result<String> {
+    match s.len() == 6 && s.bytes().all(|b| b.is_ascii_digit()) {
+        true => Ok(s.into()),
+        false => Err(anyhow!("'{}' is not a 6-digit MFA code", s)),
+    }
+}
+
+fn parse_variable_name(s: &str) -> Result<String> {
+    match !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        true => Ok(s.into()),
+        false => Err(anyhow!("'{}' is not a valid variable name", s)),
+    }
+}
+
+fn parse_env_prefix(s: &str) -> Result<String> {
+    match s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        true => parse_variable_name(s),
+        false => Err(anyhow!("'{}' is not a valid variable name prefix", s)),
+    }
 }
 
-#[derive(Debug, Deserialize, Zeroize)]
-#[serde(rename_all = "camelCase")]
-pub struct CachedSsoToken {
-    pub access_token: String,
-    pub expires_at: String,
-    pub region: String,
-    pub start_url: String,
+fn parse_duration_seconds(s: &str) -> Result<u32> {
+    let seconds: u32 = s
+        .parse()
+        .map_err(|e| anyhow!("invalid duration '{}': {}", s, e))?;
+
+    if !DURATION_SECONDS_RANGE.contains(&seconds) {
+        return Err(anyhow!(
+            "duration must be between {} and {} seconds",
+            DURATION_SECONDS_RANGE.start(),
+            DURATION_SECONDS_RANGE.end()
+        ));
+    }
+
+    Ok(seconds)
 }
 
-#[derive(Debug, Zeroize)]
-pub struct SsoCredentials {
-    pub access_key_id: String,
-    pub secret_access_key: String,
-    pub session_token: String,
-    #[zeroize(skip)]
-    pub expires_at: OffsetDateTime,
+fn parse_duration(s: &str) -> Result<u32> {
+    let duration =
+        humantime::parse_duration(s).map_err(|e| anyhow!("invalid duration '{}': {}", s, e))?;
+
+    if duration.subsec_nanos() != 0 {
+        return Err(anyhow!("duration '{}' isn't a whole number of seconds", s));
+    }
+
+    let format = |seconds: &u32| {
+        humantime::format_duration(std::time::Duration::from_secs((*seconds).into()))
+    };
+
+    match u32::try_from(duration.as_secs()) {
+        Ok(seconds) if DURATION_SECONDS_RANGE.contains(&seconds) => Ok(seconds),
+        _ => Err(anyhow!(
+            "duration must be between {} and {}",
+            format(DURATION_SECONDS_RANGE.start()),
+            format(DURATION_SECONDS_RANGE.end())
+        )),
+    }
 }
 
-impl CachedSsoToken {
-    pub fn expires_at(&self) -> Result<OffsetDateTime> {
-        OffsetDateTime::parse(self.expires_at.as_str(), &Rfc3339)
-            .map_err(|e| anyhow!("unable to parse date-time: {:?}", e))
+fn parse_max_concurrency(s: &str) -> Result<usize> {
+    match s.parse() {
+        Ok(0) => Err(anyhow!("concurrency must be at least 1")),
+        Ok(count) => Ok(count),
+        Err(e) => Err(anyhow!("invalid concurrency '{}': {}", s, e)),
     }
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    env_logger::builder()
-        .filter("h2".into(), LevelFilter::Error)
-        .filter("rustls".into(), LevelFilter::Error)
-        .filter("hyper".into(), LevelFilter::Error)
-        .filter("tracing".into(), LevelFilter::Error)
-        .filter("aws_smithy_client".into(), LevelFilter::Error)
-        .filter("aws_smithy_http_tower".into(), LevelFilter::Error)
-        .filter("aws_http".into(), LevelFilter::Error)
-        .filter("aws_endpoint".into(), LevelFilter::Error)
-        .filter("aws_config".into(), LevelFilter::Error)
-        .filter_level(LevelFilter::Debug)
-        .init();
-
-    let args = Args::from_args();
-    let profile_name: String = args.profile_name;
-
-    // first, load the SSO configuration for the given profile
-    let sso_profile = get_sso_profile(profile_name.as_str()).await?;
+async fn main() {
+    // handled ahead of argument parsing, as clap would otherwise list it in the help and the completion scripts
+    if std::env::args().nth(1).as_deref() == Some(COMPLETE_PROFILES_COMMAND) {
+        complete_profiles().await;
+        return;
+    }
 
-    log::debug!("Found SSO profile: {:#?}", sso_profile);
+    let matches = Args::clap().get_matches();
+    let mut args = Args::from_clap(&matches);
 
-    // next, see if there is a cached SSO token available in the cached tokens directory
-    if let Some(cached_sso_token) = load_cached_token(&sso_profile).await {
-        log::debug!("Loaded cached SSO token.");
+    if args.version {
+        print_version(args.verbose > 0);
+        return;
+    }
 
-        if let Ok(expires_at) = cached_sso_token.expires_at() {
-            let encoded = expires_at.format(&Rfc3339)?;
+    let status_only = matches!(
+        args.command,
+        Some(Command::Check {
+            status_only: true,
+            ..
+        })
+    );
 
-            if OffsetDateTime::now_utc() > expires_at {
-                log::error!("Cached SSO token is expired as of {}", encoded);
-                log::info!(
-                    "Run 'aws --profile {} sso login' to refresh credentials.",
-                    profile_name
-                );
-                return Ok(());
+    if let Err(e) = args.apply_settings(&matches).await {
+        if !status_only {
+            match args.error_format {
+                ErrorFormat::Text => eprintln!("Error: {:?}", e),
+                ErrorFormat::Json => eprintln!("{}", error_json(&e, args.failed_profile_name())),
             }
+        }
 
-            log::debug!("Cached SSO token is still valid, expires at {}", encoded);
+        std::process::exit(exit_code(&e));
+    }
 
-            // finally, use the sso client to fetch credentials
-            let credentials = fetch_sso_credentials(&sso_profile, &cached_sso_token)
-                .await
-                .map_err(|e| {
-                    log::error!(
-                        "Unable to fetch SSO credentials using cached SSO token: {:?}",
-                        e
-                    );
-                    e
-                })?;
+    // exits before logging is set up, so that nothing at all is written
+    if let Some(Command::Check {
+        profile_name,
+        status_only: true,
+    }) = &args.command
+    {
+        std::process::exit(token_status(&args, profile_name.as_deref()).await);
+    }
 
-            log::info!("Obtained SSO credentials, printing to standard output:");
+    init_logging(&args);
 
-            println!("# expires at {}", encoded);
-            println!("export AWS_ACCESS_KEY_ID={}", credentials.access_key_id);
-            println!(
-                "export AWS_SECRET_ACCESS_KEY={}",
-                credentials.secret_access_key
+    let result = run(&args).await;
+
+    warn_if_clock_skewed();
+
+    if let Err(e) = result {
+        if let Some(Error::HomeDirNotFound) = e.downcast_ref() {
+            log::info!(
+                "Without a home directory, set AWS_CONFIG_FILE and pass --cache-dir to locate your configuration."
             );
-            println!("export AWS_SESSION_TOKEN={}", credentials.session_token);
         }
+
+        match (args.error_format, args.log_format) {
+            (ErrorFormat::Json, _) => eprintln!("{}", error_json(&e, args.failed_profile_name())),
+            (ErrorFormat::Text, LogFormat::Text) => eprintln!("Error: {:?}", e),
+            // logged rather than printed, so that standard error holds nothing but JSON
+            (ErrorFormat::Text, LogFormat::Json) => log::error!("{:?}", e),
+        }
+
+        std::process::exit(exit_code(&e));
     }
+}
 
-    Ok(())
+/// Dependencies which are very chatty below the error level.
+const NOISY_MODULES: &[&str] = &[
+    "h2",
+    "rustls",
+    "hyper",
+    "tracing",
+    "aws_smithy_client",
+    "aws_smithy_http_tower",
+    "aws_http",
+    "aws_endpoint",
+    "aws_config",
+];
+
+/// The modules whose trace logs dump whole requests and responses, including SSO tokens and credentials, so are never
+/// logged, whatever the filters.
+const WIRE_MODULES: &[&str] = &[
+    "aws_smithy_http::middleware",
+    "aws_smithy_http_tower::dispatch",
+];
+
+/// The environment variables from which log filters are read, in `env_logger` syntax. The first one set wins.
+const LOG_ENV_VARS: &[&str] = &["AWS_SSO_ENV_LOG", "RUST_LOG"];
+
+/// What `--expires-in` prints the remaining lifetime of.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExpiresIn {
+    /// The cached SSO token.
+    Token,
+    /// The role credentials, obtained as when exporting them.
+    Credentials,
 }
 
-async fn get_sso_profile<S: AsRef<str>>(profile_name: S) -> Result<SsoProfile> {
-    // use the default filesystem and the default environment variables
-    let (fs, env) = (Fs::default(), Env::default());
+impl ExpiresIn {
+    pub const VARIANTS: &'static [&'static str] = &["token", "credentials"];
+}
 
-    // load the profile set from disk
-    let profiles = aws_config::profile::load(&fs, &env)
-        .await
-        .map_err(|e| anyhow!("unable to get profiles: {}", e))?;
-
-    // get the profile with the given name
-    //
-    // NOTE the sdk does not allow you to list profiles, which is an interesting choice, you have to _know_ what
-    //      profile you're looking for
-    if let Some(profile) = profiles.get_profile(profile_name.as_ref()) {
-        // extract all the properties, converting them to errors if not present
-        Ok(SsoProfile {
-            profile_name: profile_name.as_ref().into(),
-            region: profile
-                .get("region")
-                .ok_or(anyhow!("profile must have region property set"))?
-                .into(),
-            sso_account_id: profile
-                .get("sso_account_id")
-                .ok_or(anyhow!("profile must have sso_account_id property set"))?
-                .into(),
-            sso_region: profile
-                .get("sso_region")
-                .ok_or(anyhow!("profile must have sso_region property set"))?
-                .into(),
-            sso_role_name: profile
-                .get("sso_role_name")
-                .ok_or(anyhow!("profile must have sso_role_name property set"))?
-                .into(),
-            sso_start_url: profile
-                .get("sso_start_url")
-                .ok_or(anyhow!("profile must have sso_start_url property set"))?
-                .into(),
-        })
+impl std::str::FromStr for ExpiresIn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "token" => Ok(ExpiresIn::Token),
+            "credentials" => Ok(ExpiresIn::Credentials),
+            _ => Err(Error::Parse(format!("--expires-in '{}'", s))),
+        }
+    }
+}
+
+/// The format of the log records written to standard error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogFormat {
+    /// The human-readable format of `env_logger`.
+    Text,
+    /// A JSON object per line, with `timestamp`, `level`, `module`, and `message` fields, for log aggregation.
+    Json,
+}
+
+impl LogFormat {
+    pub const VARIANTS: &'static [&'static str] = &["text", "json"];
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::Parse(format!("log format '{}'", s))),
+        }
+    }
+}
+
+/// The format of the error written to standard error on failure.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// The error and its causes, as human-readable text.
+    Text,
+    /// A single JSON object naming the kind of error with a stable code, for scripts.
+    Json,
+}
+
+impl ErrorFormat {
+    pub const VARIANTS: &'static [&'static str] = &["text", "json"];
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(Error::Parse(format!("error format '{}'", s))),
+        }
+    }
+}
+
+/// When to color the log records written to standard error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Color {
+    /// Only when standard error is a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub const VARIANTS: &'static [&'static str] = &["auto", "always", "never"];
+}
+
+impl std::str::FromStr for Color {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => Err(Error::Parse(format!("--color '{}'", s))),
+        }
+    }
+}
+
+/// Where to read SSO tokens from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TokenBackend {
+    /// The files cached by `aws sso login`.
+    File,
+    /// The OS keyring.
+    Keyring,
+}
+
+impl TokenBackend {
+    pub const VARIANTS: &'static [&'static str] = &["file", "keyring"];
+}
+
+impl std::str::FromStr for TokenBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "file" => Ok(TokenBackend::File),
+            "keyring" => Ok(TokenBackend::Keyring),
+            _ => Err(Error::Parse(format!("--token-source '{}'", s))),
+        }
+    }
+}
+
+/// Log to standard error. The built-in defaults are overridden by the filters in the environment, which are in turn
+/// overridden by `--quiet` and `--verbose`.
+fn init_logging(args: &Args) {
+    let mut builder = env_logger::Builder::new();
+
+    // logs must never be written to standard output, which is reserved for credentials
+    builder.target(Target::Stderr);
+
+    let color = if args.no_color {
+        Some(Color::Never)
     } else {
-        // the profile was not found
-        Err(anyhow!("profile '{}' not found", profile_name.as_ref()))
+        args.color
+    };
+
+    match color {
+        Some(Color::Auto) => builder.write_style(WriteStyle::Auto),
+        Some(Color::Always) => builder.write_style(WriteStyle::Always),
+        Some(Color::Never) => builder.write_style(WriteStyle::Never),
+        None => match std::env::var("RUST_LOG_STYLE") {
+            Ok(style) => builder.parse_write_style(style.as_str()),
+            Err(_) => builder.write_style(WriteStyle::Auto),
+        },
+    };
+
+    if args.log_format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "module": record.module_path().unwrap_or_default(),
+                "message": record.args().to_string(),
+            });
+
+            writeln!(buf, "{}", line)
+        });
+    }
+
+    builder.filter_level(LevelFilter::Debug);
+
+    for module in NOISY_MODULES {
+        builder.filter(Some(module), LevelFilter::Error);
+    }
+
+    if let Some(filters) = LOG_ENV_VARS
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    {
+        builder.parse_filters(filters.as_str());
+    }
+
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => Some(LevelFilter::Error),
+        (false, 0) => None,
+        (false, _) => Some(LevelFilter::Trace),
+    };
+
+    if let Some(level) = level {
+        builder.filter_level(level);
+
+        // dependencies are quietened along with everything else, but only made more verbose when asked for twice
+        if args.quiet || args.verbose >= 2 {
+            for module in NOISY_MODULES {
+                builder.filter(Some(module), level);
+            }
+        }
+    }
+
+    let logger = builder.build();
+
+    log::set_max_level(logger.filter());
+
+    if let Err(e) = log::set_boxed_logger(Box::new(WireLogFilter(logger))) {
+        eprintln!("Unable to initialize logging: {}", e);
     }
 }
 
-async fn load_cached_token(sso_profile: &SsoProfile) -> Option<CachedSsoToken> {
-    let cache_dir = dirs::home_dir()
-        .expect("unable to get the current user's home dir")
-        .join(".aws")
-        .join("sso")
-        .join("cache");
+/// Drops the trace logs of the [`WIRE_MODULES`], whatever the filters of the logger it wraps allow.
+struct WireLogFilter(env_logger::Logger);
 
-    if !cache_dir.is_dir() {
-        log::debug!(
-            "SSO credentials cache directory does not exist: {}",
-            cache_dir.display()
-        );
-        return None;
+impl WireLogFilter {
+    fn is_wire_trace(metadata: &log::Metadata) -> bool {
+        metadata.level() == log::Level::Trace
+            && WIRE_MODULES.iter().any(|module| {
+                metadata.target() == *module
+                    || metadata.target().starts_with(&format!("{}::", module))
+            })
     }
+}
 
-    let cache_filename = format!(
-        "{}.json",
-        Sha1::from(sso_profile.sso_start_url.as_str()).hexdigest()
-    );
+impl log::Log for WireLogFilter {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        !Self::is_wire_trace(metadata) && self.0.enabled(metadata)
+    }
 
-    let cache_file = cache_dir.join(cache_filename);
+    fn log(&self, record: &log::Record) {
+        if !Self::is_wire_trace(record.metadata()) {
+            self.0.log(record);
+        }
+    }
 
-    if !cache_file.is_file() {
-        log::debug!(
-            "Cache file for profile '{}' does not exist.",
-            sso_profile.profile_name
-        );
-        return None;
+    fn flush(&self) {
+        self.0.flush();
     }
+}
 
-    tokio::fs::read_to_string(cache_file)
-        .await
-        .map(|s| {
-            serde_json::from_str::<CachedSsoToken>(s.as_str())
-                .map_err(|e| log::error!("Unable to deserialize cached SSO token: {:?}", e))
-                .ok()
-        })
-        .ok()
-        .flatten()
-}
-
-async fn fetch_sso_credentials(
-    profile: &SsoProfile,
-    token: &CachedSsoToken,
-) -> Result<SsoCredentials> {
-    let config = SsoConfig::builder()
-        .region(SsoRegion::new(token.region.clone()))
-        .build();
-
-    let client = SsoClient::from_conf(config);
-
-    let role_credentials = client
-        .get_role_credentials()
-        .account_id(profile.sso_account_id.clone())
-        .role_name(profile.sso_role_name.clone())
-        .access_token(token.access_token.clone())
-        .send()
-        .await?
-        .role_credentials
-        .ok_or(anyhow!("response did not contain any credentials"))?;
-
-    Ok(SsoCredentials {
-        access_key_id: role_credentials
-            .access_key_id
-            .ok_or(anyhow!("response did not contain an access key id"))?,
-        secret_access_key: role_credentials
-            .secret_access_key
-            .ok_or(anyhow!("response did not contain a secret access key"))?,
-        session_token: role_credentials
-            .session_token
-            .ok_or(anyhow!("response did not contain a session token"))?,
-        expires_at: OffsetDateTime::from_unix_timestamp_nanos(role_credentials.expiration.into())
-            .map_err(|e| {
-            anyhow!(
-                "unable to parse expiration date from role credentials: {:?}",
-                e
-            )
-        })?,
-    })
+/// The profiles for which credentials couldn't be obtained when exporting credentials for several at once.
+#[derive(Debug)]
+struct ProfileFailures(Vec<(String, anyhow::Error)>);
+
+impl std::fmt::Display for ProfileFailures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unable to obtain credentials for {} profile(s):",
+            self.0.len()
+        )?;
+
+        for (profile_name, e) in self.0.iter() {
+            write!(f, "\n  {}: {:#}", profile_name, e)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProfileFailures {}
+
+/// The error as the JSON object printed by `--error-format json`: the [`Error::code`] of the error, or `Other` for
+/// errors outside the crate, its message along with its causes, and the profile it's about, if known. Failures for
+/// several profiles are listed under `failures` in the same form.
+fn error_json(e: &anyhow::Error, profile_name: Option<String>) -> serde_json::Value {
+    if let Some(ProfileFailures(failures)) = e.downcast_ref() {
+        return serde_json::json!({
+            "error": "ProfileFailures",
+            "message": format!("{:#}", e),
+            "profile": null,
+            "failures": failures
+                .iter()
+                .map(|(profile_name, e)| error_json(e, Some(profile_name.clone())))
+                .collect::<Vec<_>>(),
+        });
+    }
+
+    let error = e.downcast_ref::<Error>();
+
+    serde_json::json!({
+        "error": error.map_or("Other", Error::code),
+        "message": format!("{:#}", e),
+        "profile": error.and_then(Error::profile_name).map(String::from).or(profile_name),
+    })
+}
+
+/// The exit code for the given error, as documented in the README, so that wrapper scripts can branch on it.
+fn exit_code(e: &anyhow::Error) -> i32 {
+    // several failures share an exit code only if they all failed for the same reason
+    if let Some(ProfileFailures(failures)) = e.downcast_ref() {
+        let mut codes = failures.iter().map(|(_, e)| exit_code(e));
+        let first = codes.next().unwrap_or(1);
+
+        return match codes.all(|code| code == first) {
+            true => first,
+            false => 1,
+        };
+    }
+
+    match e.downcast_ref::<Error>() {
+        Some(Error::ProfileNotFound(_)) => 2,
+        Some(Error::CacheTokenExpired(_)) => 3,
+        Some(Error::CacheTokenMissing(_))
+        | Some(Error::CacheTokenUnreadable { .. })
+        | Some(Error::KeyringTokenUnreadable { .. }) => 4,
+        Some(Error::CacheTokenUnauthorized(_)) => 5,
+        Some(Error::StsApi { .. }) => 6,
+        _ => 1,
+    }
+}
+
+async fn run(args: &Args) -> Result<()> {
+    // fail before obtaining any credentials, rather than having nowhere to write them
+    if let Some(fd) = args.output_fd {
+        check_writable_fd(fd)?;
+    }
+
+    match &args.command {
+        Some(Command::ListProfiles) => list_profiles().await,
+        Some(Command::Check { profile_name, .. }) => {
+            let profile_name = args.resolve_profile_name(profile_name.as_deref())?;
+
+            check(args, profile_name.as_str()).await
+        }
+        Some(Command::Accounts { profile_name, json }) => {
+            let profile_name = args.resolve_profile_name(profile_name.as_deref())?;
+
+            list_sso_accounts(args, profile_name.as_str(), *json).await
+        }
+        Some(Command::Roles { profile_name, json }) => {
+            let profile_name = args.resolve_profile_name(profile_name.as_deref())?;
+
+            list_roles(args, profile_name.as_str(), *json).await
+        }
+        Some(Command::Unset { profile_names }) => {
+            let extra: Vec<&str> = std::iter::once(OUTPUT_VARIABLE)
+                .chain(args.print_account_alias.then_some(ACCOUNT_ALIAS_VARIABLE))
+                .chain(
+                    args.passthrough_key
+                        .iter()
+                        .map(|passthrough| passthrough.variable.as_str()),
+                )
+                .collect();
+
+            let names = match profile_names.as_slice() {
+                [_, _, ..] => profile_names
+                    .iter()
+                    .flat_map(|profile_name| {
+                        variable_names(
+                            args.expiration_var_name.as_slice(),
+                            true,
+                            extra.as_slice(),
+                            args.env_prefix.as_deref(),
+                            Some(variable_suffix(profile_name).as_str()),
+                        )
+                    })
+                    .collect(),
+                _ => variable_names(
+                    args.expiration_var_name.as_slice(),
+                    true,
+                    extra.as_slice(),
+                    args.env_prefix.as_deref(),
+                    None,
+                ),
+            };
+
+            print!("{}", args.format().render_unset(names.as_slice())?);
+
+            Ok(())
+        }
+        Some(Command::Store { profile_name }) => {
+            let profile_name = args.resolve_profile_name(profile_name.as_deref())?;
+
+            store_credentials(args, profile_name.as_str()).await
+        }
+        Some(Command::Load { profile_name }) => {
+            let profile_name = args.resolve_profile_name(profile_name.as_deref())?;
+
+            load_credentials(args, profile_name.as_str()).await
+        }
+        Some(Command::Completions { shell }) => {
+            let mut script = Vec::new();
+
+            Args::clap().gen_completions_to(env!("CARGO_PKG_NAME"), *shell, &mut script);
+
+            print!(
+                "{}",
+                complete_profile_names(*shell, String::from_utf8(script)?)
+            );
+
+            Ok(())
+        }
+        None if args.print_env_names => print_env_names(args).await,
+        None if args.dry_run => dry_run(args).await,
+        None if args.watch => watch(args).await,
+        None => match args.expires_in {
+            Some(expires_in) => print_expires_in(args, expires_in).await,
+            None => export_credentials(args).await.map(drop),
+        },
+    }
+}
+
+/// Print the crate version with the commit and time of the build, and if verbose, the AWS SDK crate versions.
+fn print_version(verbose: bool) {
+    println!("aws-sso-env {}", env!("CARGO_PKG_VERSION"));
+    println!("commit: {}", env!("AWS_SSO_ENV_COMMIT"));
+    println!("built: {}", env!("AWS_SSO_ENV_BUILT_AT"));
+
+    if verbose {
+        for sdk_version in env!("AWS_SSO_ENV_SDK_VERSIONS").split(',') {
+            println!("{}", sdk_version);
+        }
+    }
+}
+
+/// The hidden subcommand used by the completion scripts to list the profiles in the AWS configuration file.
+const COMPLETE_PROFILES_COMMAND: &str = "__complete-profiles";
+
+/// Print the name of every profile in the AWS configuration file, one per line.
+async fn complete_profiles() {
+    // completion must never fail noisily, so a missing or unreadable config file just completes nothing
+    if let Ok(profiles) = load_config_profiles().await {
+        for name in profiles.keys() {
+            println!("{}", name);
+        }
+    }
+}
+
+/// Make the profile name arguments in a generated completion script complete against the profiles in the AWS
+/// configuration file, by calling the hidden `__complete-profiles` subcommand, as clap only generates completions for
+/// static values.
+fn complete_profile_names(shell: Shell, script: String) -> String {
+    let bin = env!("CARGO_PKG_NAME");
+    let command = format!("{} {}", bin, COMPLETE_PROFILES_COMMAND);
+
+    match shell {
+        // clap names the subcommand cases after the binary with its hyphens doubled, which the command never matches
+        Shell::Bash => script
+            .replace(
+                format!("{}__", bin.replace('-', "__")).as_str(),
+                format!("{}__", bin).as_str(),
+            )
+            .replace("<profile-name>...", "<profile-name>")
+            .replace(
+                "<profile-name>",
+                format!("$({} 2>/dev/null)", command).as_str(),
+            ),
+        Shell::Zsh => script
+            .lines()
+            .map(|line| match line.strip_suffix(":_files' \\") {
+                Some(line) if line.starts_with("'::profile-name") => format!(
+                    "{}:{{compadd -- ${{(f)\"$({} 2>/dev/null)\"}}}}' \\\n",
+                    line, command
+                ),
+                _ => format!("{}\n", line),
+            })
+            .collect(),
+        Shell::Fish => {
+            let mut script = script;
+
+            for condition in [
+                "__fish_use_subcommand",
+                "__fish_seen_subcommand_from accounts check roles unset",
+            ] {
+                script.push_str(
+                    format!(
+                        "complete -c {} -n \"{}\" -f -a \"({} 2>/dev/null)\"\n",
+                        bin, condition, command
+                    )
+                    .as_str(),
+                );
+            }
+
+            script
+        }
+        Shell::PowerShell => {
+            let profiles = format!(
+                concat!(
+                    "\n            {} 2>$null | ForEach-Object {{",
+                    "\n                [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)",
+                    "\n            }}",
+                ),
+                command
+            );
+
+            [
+                format!("'{}' {{", bin),
+                format!("'{};accounts' {{", bin),
+                format!("'{};check' {{", bin),
+                format!("'{};roles' {{", bin),
+                format!("'{};unset' {{", bin),
+            ]
+            .iter()
+            .fold(script, |script, block| {
+                script.replace(block.as_str(), format!("{}{}", block, profiles).as_str())
+            })
+        }
+        _ => script,
+    }
+}
+
+/// Print the accounts the profile's cached SSO token grants access to, one per line with their names, or as JSON.
+async fn list_sso_accounts(args: &Args, profile_name: &str, json: bool) -> Result<()> {
+    let sso_profile = args.sso_profile(profile_name).await?;
+    let token = load_valid_token(args, profile_name, &sso_profile).await?;
+
+    let client = sso_client(&token, &args.fetch_options())?;
+    let accounts = list_accounts(&client, &sso_profile, &token).await?;
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&accounts)?),
+        false => accounts.iter().for_each(|account| {
+            println!(
+                "{}\t{}",
+                account.account_id,
+                account.account_name.as_deref().unwrap_or_default()
+            )
+        }),
+    }
+
+    Ok(())
+}
+
+/// Print the roles the profile's cached SSO token grants access to in its account, one per line or as JSON.
+async fn list_roles(args: &Args, profile_name: &str, json: bool) -> Result<()> {
+    let sso_profile = args.sso_profile(profile_name).await?;
+
+    // the roles are those within the profile's account, though it needn't have a role itself
+    sso_profile.account_id()?;
+
+    let token = load_valid_token(args, profile_name, &sso_profile).await?;
+
+    let client = sso_client(&token, &args.fetch_options())?;
+    let roles = list_account_roles(&client, &sso_profile, &token).await?;
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&roles)?),
+        false => roles.iter().for_each(|role| println!("{}", role.role_name)),
+    }
+
+    Ok(())
+}
+
+/// The exit code of `check --status-only` when the cached SSO token has expired.
+const STATUS_EXPIRED: i32 = 10;
+
+/// The exit code of `check --status-only` when no SSO token is cached for the profile.
+const STATUS_MISSING: i32 = 11;
+
+/// The exit code describing whether the profile has a valid cached SSO token, without printing anything. A broken
+/// profile exits with the same code as it would otherwise.
+async fn token_status(args: &Args, profile_name: Option<&str>) -> i32 {
+    let status = async {
+        let profile_name = args.resolve_profile_name(profile_name)?;
+        let sso_profile = args.sso_profile(profile_name.as_str()).await?;
+
+        let code = match args.cached_token(&sso_profile).await? {
+            None => STATUS_MISSING,
+            Some(token) if args.expiry_cutoff() > token.expires_at => STATUS_EXPIRED,
+            Some(_) => 0,
+        };
+
+        Ok::<_, anyhow::Error>(code)
+    };
+
+    status.await.unwrap_or_else(|e| exit_code(&e))
+}
+
+/// Report whether the profile is well-formed and whether it has a valid cached SSO token, never printing any secrets.
+async fn check(args: &Args, profile_name: &str) -> Result<()> {
+    let sso_profile = args.sso_profile(profile_name).await?;
+
+    println!("profile ok");
+
+    if args.debug_cache {
+        debug_cache(args, &sso_profile).await?;
+    }
+
+    let cached_sso_token = match args.cached_token(&sso_profile).await? {
+        Some(cached_sso_token) => cached_sso_token,
+        None => {
+            let e = args.missing_token(profile_name, &sso_profile).await?;
+
+            match e {
+                Error::CacheTokenUnreadable { .. } | Error::KeyringTokenUnreadable { .. } => {
+                    println!("token unreadable")
+                }
+                _ => println!("token missing"),
+            }
+
+            return Err(e.into());
+        }
+    };
+
+    let expires_at = cached_sso_token.expires_at;
+    let encoded = expires_at.format(&Rfc3339)?;
+
+    if args.expiry_cutoff() > expires_at {
+        println!("token expired");
+        return Err(Error::CacheTokenExpired(encoded).into());
+    }
+
+    println!("token valid until {}", encoded);
+
+    Ok(())
+}
+
+/// Print the name of every profile in the AWS configuration file and whether it looks like an SSO profile.
+async fn list_profiles() -> Result<()> {
+    let profiles = load_config_profiles().await?;
+
+    let width = profiles.keys().map(String::len).max().unwrap_or(0);
+
+    for (name, properties) in profiles.iter() {
+        let kind = if is_sso_profile(properties) {
+            "sso"
+        } else {
+            "-"
+        };
+
+        println!("{:width$}  {}", name, kind, width = width);
+    }
+
+    Ok(())
+}
+
+/// Credentials obtained for a profile, along with the profile and when its SSO token expires.
+struct Obtained {
+    sso_profile: SsoProfile,
+    credentials: SsoCredentials,
+    token_expires_at: String,
+    /// The alias of the account, if asked for and it has one.
+    account_alias: Option<String>,
+}
+
+impl Obtained {
+    fn exports<'a>(&'a self, args: &'a Args, suffix: Option<&'a str>) -> Exports<'a> {
+        Exports {
+            profile: &self.sso_profile,
+            credentials: &self.credentials,
+            token_expires_at: self.token_expires_at.as_str(),
+            expiration_variables: args.expiration_var_name.as_slice(),
+            region: args.region(&self.sso_profile),
+            account_alias: self.account_alias.as_deref(),
+            prefix: args.env_prefix.as_deref(),
+            suffix,
+            passthrough: args.passthrough_key.as_slice(),
+            comments: !args.no_comments,
+        }
+    }
+}
+
+/// Print the names of the variables that exporting the profiles' credentials would set, reflecting the region options and
+/// suffixing them when there are several profiles.
+async fn print_env_names(args: &Args) -> Result<()> {
+    let profile_names = args.profile_names()?;
+    let suffixed = profile_names.len() > 1;
+
+    for profile_name in profile_names.iter() {
+        let sso_profile = args.sso_profile(profile_name).await?;
+        let suffix = suffixed.then(|| variable_suffix(profile_name));
+
+        let extra: Vec<&str> = sso_profile
+            .output
+            .as_ref()
+            .map(|_| OUTPUT_VARIABLE)
+            .into_iter()
+            .chain(args.print_account_alias.then_some(ACCOUNT_ALIAS_VARIABLE))
+            .chain(
+                args.passthrough_key
+                    .iter()
+                    .filter(|passthrough| sso_profile.properties.contains_key(&passthrough.key))
+                    .map(|passthrough| passthrough.variable.as_str()),
+            )
+            .collect();
+
+        let credentials_only = args.format().is_credentials_only();
+
+        for name in variable_names(
+            args.expiration_var_name.as_slice(),
+            args.region(&sso_profile).is_some() && !credentials_only,
+            if credentials_only {
+                &[]
+            } else {
+                extra.as_slice()
+            },
+            args.env_prefix.as_deref(),
+            suffix.as_deref(),
+        ) {
+            println!("{}", name);
+        }
+    }
+
+    Ok(())
+}
+
+async fn export_credentials(args: &Args) -> Result<OffsetDateTime> {
+    let profile_names = args.profile_names()?;
+
+    if let [profile_name] = profile_names.as_slice() {
+        return export_profile(args, profile_name.as_str()).await;
+    }
+
+    if args.credential_process || args.write_profile.is_some() {
+        return Err(anyhow!(
+            "--credential-process and --write-profile only support a single profile"
+        ));
+    }
+
+    if args.account_id.is_some() || args.role_name.is_some() || args.assume_role.is_some() {
+        return Err(anyhow!(
+            "--account-id, --role-name, and --assume-role only support a single profile"
+        ));
+    }
+
+    // each profile is rendered into a buffer of its own, already large enough to never be reallocated
+    let mut rendered = Vec::with_capacity(profile_names.len());
+    let mut failures = Vec::new();
+    let mut expires_at: Option<OffsetDateTime> = None;
+
+    let concurrency = match args.login {
+        true => 1,
+        false => args.max_concurrency,
+    };
+
+    // profiles in the same region share a client, and with it the connections to SSO
+    let clients = SsoClients::default();
+    let clients = &clients;
+
+    // fetched concurrently but yielded in the order given, so that the output doesn't depend on which finishes first
+    let mut results = futures::stream::iter(profile_names)
+        .map(|profile_name| async move {
+            let obtained =
+                obtain_credentials_sharing(args, profile_name.as_str(), Some(clients)).await;
+            (profile_name, obtained)
+        })
+        .buffered(concurrency);
+
+    while let Some((profile_name, obtained)) = results.next().await {
+        // keep going, so that one expired token doesn't hold up the credentials for every other profile
+        let obtained = match obtained {
+            Ok(obtained) => obtained,
+            Err(e) => {
+                failures.push((profile_name, e));
+                continue;
+            }
+        };
+
+        let suffix = variable_suffix(profile_name.as_str());
+
+        expires_at = Some(match expires_at {
+            Some(expires_at) => expires_at.min(obtained.credentials.expires_at),
+            None => obtained.credentials.expires_at,
+        });
+
+        rendered.push(Zeroizing::new(
+            args.format()
+                .render(&obtained.exports(args, Some(suffix.as_str())))?,
+        ));
+    }
+
+    // joined into one buffer sized exactly, so that the secrets aren't left behind when it grows
+    let mut output = String::with_capacity(rendered.iter().map(|rendered| rendered.len()).sum());
+
+    for rendered in &rendered {
+        output.push_str(rendered.as_str());
+    }
+
+    let result = emit(args, output.as_str()).await;
+
+    output.zeroize();
+
+    result?;
+
+    match (failures.is_empty(), expires_at) {
+        (true, Some(expires_at)) => Ok(expires_at),
+        _ => Err(ProfileFailures(failures).into()),
+    }
+}
+
+/// Print the whole number of seconds until the profile's cached SSO token or credentials expire, and nothing else.
+async fn print_expires_in(args: &Args, expires_in: ExpiresIn) -> Result<()> {
+    let profile_name = match args.profile_names()?.as_slice() {
+        [profile_name] => profile_name.clone(),
+        _ => return Err(anyhow!("--expires-in only supports a single profile")),
+    };
+
+    let expires_at = match expires_in {
+        ExpiresIn::Token => {
+            let sso_profile = args.sso_profile(profile_name.as_str()).await?;
+
+            match args.cached_token(&sso_profile).await? {
+                Some(token) => token.expires_at,
+                None => {
+                    return Err(args
+                        .missing_token(profile_name.as_str(), &sso_profile)
+                        .await?
+                        .into())
+                }
+            }
+        }
+        ExpiresIn::Credentials => {
+            obtain_credentials(args, profile_name.as_str())
+                .await?
+                .credentials
+                .expires_at
+        }
+    };
+
+    let seconds = (expires_at - OffsetDateTime::now_utc()).whole_seconds();
+
+    match args.allow_negative {
+        true => println!("{}", seconds),
+        false => println!("{}", seconds.max(0)),
+    }
+
+    Ok(())
+}
+
+/// Export the credentials for a single profile, in whichever way the arguments ask for, returning when they expire.
+async fn export_profile(args: &Args, profile_name: &str) -> Result<OffsetDateTime> {
+    let obtained = obtain_credentials(args, profile_name).await?;
+    let expires_at = obtained.credentials.expires_at;
+
+    if let Some(write_profile) = args.write_profile.as_deref() {
+        let path = write_credentials_file(&obtained.credentials, write_profile).await?;
+
+        log::info!(
+            "Wrote SSO credentials to profile '{}' in {}",
+            write_profile,
+            path.display()
+        );
+
+        return Ok(expires_at);
+    }
+
+    if args.credential_process {
+        log::info!("Obtained SSO credentials, printing to standard output:");
+
+        let output = CredentialProcessOutput::new(&obtained.credentials)?;
+
+        let mut json = serde_json::to_string(&output)?;
+
+        println!("{}", json);
+
+        json.zeroize();
+
+        return Ok(expires_at);
+    }
+
+    let mut output = args.format().render(&obtained.exports(args, None))?;
+
+    let result = emit(args, output.as_str()).await;
+
+    output.zeroize();
+
+    result.map(|_| expires_at)
+}
+
+/// The OS keyring, read and written using the tool named by `AWS_SSO_ENV_KEYRING_COMMAND` if set.
+fn keyring() -> Keyring {
+    match std::env::var(KEYRING_COMMAND_ENV_VAR) {
+        Ok(program) => Keyring { program },
+        Err(_) => Keyring::default(),
+    }
+}
+
+/// Obtain credentials for the profile and store them in the keyring, under the profile's name.
+async fn store_credentials(args: &Args, profile_name: &str) -> Result<()> {
+    let obtained = obtain_credentials(args, profile_name).await?;
+
+    let stored = StoredCredentials {
+        credentials: obtained.credentials,
+        account_id: obtained.sso_profile.account_id()?.into(),
+        role_name: obtained.sso_profile.role_name()?.into(),
+        account_alias: obtained.account_alias,
+        token_expires_at: obtained.token_expires_at,
+    };
+
+    keyring().store_credentials(profile_name, &stored).await?;
+
+    log::info!(
+        "Stored SSO credentials for profile '{}' in the keyring.",
+        profile_name
+    );
+
+    Ok(())
+}
+
+/// Export the credentials stored in the keyring for the profile, failing if there are none or they have expired.
+async fn load_credentials(args: &Args, profile_name: &str) -> Result<()> {
+    let stored = keyring()
+        .load_credentials(profile_name)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "no credentials are stored in the keyring for profile '{}': run `aws-sso-env store {}`",
+                profile_name,
+                profile_name
+            )
+        })?;
+
+    if stored.credentials.expires_at <= args.expiry_cutoff() {
+        return Err(anyhow!(
+            "the credentials stored in the keyring for profile '{}' expired at {}: run `aws-sso-env store {}` again",
+            profile_name,
+            stored.credentials.expiration()?,
+            profile_name
+        ));
+    }
+
+    // the profile is still read for its region and passthrough keys, but the credentials may be for another role
+    let mut sso_profile = args.sso_profile(profile_name).await?;
+
+    sso_profile.sso_account_id = Some(stored.account_id.clone());
+    sso_profile.sso_role_name = Some(stored.role_name.clone());
+
+    let obtained = Obtained {
+        sso_profile,
+        credentials: stored.credentials,
+        token_expires_at: stored.token_expires_at,
+        account_alias: stored.account_alias,
+    };
+
+    let mut output = args.format().render(&obtained.exports(args, None))?;
+
+    let result = emit(args, output.as_str()).await;
+
+    output.zeroize();
+
+    result
+}
+
+/// How long `--watch` waits at least before exporting credentials again, whether the last attempt failed or the
+/// credentials were already within the expiry margin, so that SSO isn't called in a tight loop.
+const WATCH_MIN_INTERVAL: time::Duration = time::Duration::seconds(60);
+
+/// Export credentials to a file, then keep exporting fresh ones shortly before they expire until interrupted.
+async fn watch(args: &Args) -> Result<()> {
+    if args.output.is_none() && args.write_profile.is_none() {
+        return Err(anyhow!(
+            "--watch requires --output or --write-profile, as credentials printed to standard output can't be \
+             replaced when they expire"
+        ));
+    }
+
+    tokio::select! {
+        result = keep_refreshing(args) => result,
+        interrupted = tokio::signal::ctrl_c() => {
+            interrupted?;
+
+            log::info!("Interrupted, no longer refreshing credentials.");
+
+            Ok(())
+        }
+    }
+}
+
+/// Export credentials over and over, each time sleeping until shortly before they expire. Only the first export
+/// failing is fatal, as a later failure, e.g. a network outage, may well be over by the next attempt.
+async fn keep_refreshing(args: &Args) -> Result<()> {
+    let mut expires_at = export_credentials(args).await?;
+
+    loop {
+        let refresh_at = (expires_at - time::Duration::seconds(args.expiry_margin.into()))
+            .max(OffsetDateTime::now_utc() + WATCH_MIN_INTERVAL);
+
+        log::info!(
+            "Refreshing credentials at {}.",
+            refresh_at.format(&Rfc3339)?
+        );
+
+        let delay = refresh_at - OffsetDateTime::now_utc();
+
+        tokio::time::sleep(delay.try_into().unwrap_or_default()).await;
+
+        expires_at = match export_credentials(args).await {
+            Ok(expires_at) => expires_at,
+            Err(e) => {
+                log::error!("Unable to refresh credentials: {:?}", e);
+
+                // try again after the minimum interval
+                OffsetDateTime::now_utc()
+            }
+        };
+    }
+}
+
+/// Print the rendered credentials, or write them to the file given by `--output` or descriptor given by `--output-fd`.
+async fn emit(args: &Args, output: &str) -> Result<()> {
+    if let Some(fd) = args.output_fd {
+        write_to_fd(fd, output.as_bytes())?;
+
+        log::info!("Wrote SSO credentials to file descriptor {}", fd);
+
+        return Ok(());
+    }
+
+    match args.output.as_deref() {
+        Some(path) => {
+            replace_private_file(path, output.as_bytes()).await?;
+
+            log::info!("Wrote SSO credentials to {}", path.display());
+        }
+        None => {
+            log::info!("Obtained SSO credentials, printing to standard output:");
+
+            print!("{}", output);
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the profile to fetch credentials for, using the account and role given instead if any, failing if it lacks
+/// either.
+async fn credentials_profile(args: &Args, profile_name: &str) -> Result<SsoProfile> {
+    let mut sso_profile = args.sso_profile(profile_name).await?;
+
+    if let Some(account_id) = args.account_id.as_deref() {
+        sso_profile.sso_account_id = Some(account_id.into());
+    }
+
+    if let Some(role_name) = args.role_name.as_deref() {
+        sso_profile.sso_role_name = Some(role_name.into());
+    }
+
+    log::debug!("Found SSO profile: {:#?}", sso_profile);
+
+    // unlike checking or listing, fetching credentials needs both an account and a role, so fail before any logging in
+    sso_profile.account_id()?;
+    sso_profile.role_name()?;
+
+    Ok(sso_profile)
+}
+
+/// Find a usable SSO token with which to fetch credentials for the profile, as loaded by [`credentials_profile`].
+async fn load_credentials_token(
+    args: &Args,
+    profile_name: &str,
+    sso_profile: &SsoProfile,
+) -> Result<CachedSsoToken> {
+    let cached_sso_token = load_valid_token(args, profile_name, sso_profile).await?;
+
+    log::debug!(
+        "Cached SSO token is still valid, expires at {}",
+        cached_sso_token.expires_at.format(&Rfc3339)?
+    );
+
+    // every token source has already checked that the token was issued for the profile's start url, as it only grants
+    // access to accounts within that portal
+    Ok(cached_sso_token)
+}
+
+/// Report that a valid SSO token exists for each profile and which account and role credentials would be fetched for,
+/// without fetching them or otherwise calling AWS.
+async fn dry_run(args: &Args) -> Result<()> {
+    for profile_name in args.profile_names()? {
+        let sso_profile = credentials_profile(args, profile_name.as_str()).await?;
+
+        args.check_strict(profile_name.as_str(), &sso_profile)?;
+
+        let cached_sso_token =
+            load_credentials_token(args, profile_name.as_str(), &sso_profile).await?;
+
+        println!(
+            "dry run: cached SSO token for profile '{}' is valid until {}",
+            profile_name,
+            cached_sso_token.expires_at.format(&Rfc3339)?
+        );
+        println!(
+            "dry run: would fetch credentials for account {} role {}",
+            sso_profile.account_id()?,
+            sso_profile.role_name()?
+        );
+
+        if let Some(role_arn) = args.assume_role.as_deref() {
+            println!("dry run: would then assume role {}", role_arn);
+        }
+    }
+
+    Ok(())
+}
+
+/// Obtain credentials for the given profile, reusing cached credentials where possible.
+async fn obtain_credentials(args: &Args, profile_name: &str) -> Result<Obtained> {
+    obtain_credentials_sharing(args, profile_name, None).await
+}
+
+/// Obtain credentials for the given profile as with [`obtain_credentials`], fetching them with the SSO client for the
+/// token's region among the given clients, if any, so that several profiles can share one.
+async fn obtain_credentials_sharing(
+    args: &Args,
+    profile_name: &str,
+    clients: Option<&SsoClients>,
+) -> Result<Obtained> {
+    let obtaining = Instant::now();
+
+    // first, load the SSO configuration for the given profile, using the account and role given instead if any
+    let started = Instant::now();
+    let mut sso_profile = credentials_profile(args, profile_name).await?;
+    args.timing("profile", profile_name, started);
+
+    args.check_strict(profile_name, &sso_profile)?;
+
+    let cache_dir = args.cache_dir()?;
+    let expiry_cutoff = args.expiry_cutoff();
+
+    // next, find a usable SSO token
+    let started = Instant::now();
+    let cached_sso_token = load_credentials_token(args, profile_name, &sso_profile).await?;
+    args.timing("token", profile_name, started);
+    let encoded = cached_sso_token.expires_at.format(&Rfc3339)?;
+
+    // reuse previously fetched credentials if they will remain valid for long enough, keyed by the start url, account,
+    // and role they're for, so that those of an overridden or edited account or role are never reused
+    let credentials_cache_file = credentials_cache_file(cache_dir.as_path(), &sso_profile)?;
+
+    let valid_until = expiry_cutoff.max(
+        OffsetDateTime::now_utc()
+            + time::Duration::seconds(args.duration_seconds.unwrap_or(0).into()),
+    );
+
+    let started = Instant::now();
+
+    let cached_credentials = if args.refresh || args.no_cache {
+        None
+    } else if args.strict {
+        parse_cached_credentials(credentials_cache_file.as_path(), &sso_profile)
+            .await?
+            .filter(|credentials| credentials.expires_at > valid_until)
+    } else {
+        load_cached_credentials(credentials_cache_file.as_path(), &sso_profile)
+            .await
+            .filter(|credentials| credentials.expires_at > valid_until)
+    };
+
+    args.timing("credentials-cache", profile_name, started);
+
+    let credentials = match cached_credentials {
+        Some(credentials) => {
+            log::debug!("Reusing cached SSO credentials.");
+            credentials
+        }
+        None if args.offline => {
+            return Err(anyhow!(
+                "no cached credentials for profile '{}' are valid for long enough, and --offline prevents fetching \
+                 them",
+                profile_name
+            ));
+        }
+        None => {
+            // finally, use the sso client to fetch credentials
+            let started = Instant::now();
+            let options = args.fetch_options();
+
+            let fetched = match clients {
+                Some(clients) => {
+                    let client = clients.client(&cached_sso_token, &options)?;
+
+                    fetch_sso_credentials_with_client(
+                        &client,
+                        &sso_profile,
+                        &cached_sso_token,
+                        &options,
+                    )
+                    .await
+                }
+                None => fetch_sso_credentials(&sso_profile, &cached_sso_token, &options).await,
+            };
+
+            let credentials = fetched.map_err(|e| {
+                log::error!(
+                    "Unable to fetch SSO credentials using cached SSO token: {:?}",
+                    e
+                );
+                e
+            })?;
+
+            args.timing("sso", profile_name, started);
+
+            // failing to cache the credentials only makes the next run slower, so isn't an error
+            if args.no_cache {
+                log::debug!("Not caching SSO credentials.");
+            } else if let Err(e) = save_cached_credentials(
+                credentials_cache_file.as_path(),
+                &sso_profile,
+                &credentials,
+            )
+            .await
+            {
+                log::warn!("Unable to cache SSO credentials: {:?}", e);
+            }
+
+            credentials
+        }
+    };
+
+    // the credentials exported are then those of the assumed role, described as such
+    let credentials = match args.assume_role.as_deref() {
+        Some(role_arn) => {
+            let options = AssumeRoleOptions {
+                role_arn: role_arn.into(),
+                external_id: args.external_id.clone(),
+                mfa_serial: args.mfa_serial.clone(),
+                token_code: args.token_code.clone(),
+                role_session_name: args
+                    .role_session_name
+                    .clone()
+                    .unwrap_or_else(|| ROLE_SESSION_NAME.into()),
+                region: args
+                    .region(&sso_profile)
+                    .unwrap_or(sso_profile.sso_region.as_str())
+                    .into(),
+                endpoint_url: args.sts_endpoint_url.clone(),
+                variant: args.endpoint_variant(),
+            };
+
+            log::debug!("Assuming role {} using the SSO credentials.", role_arn);
+
+            let started = Instant::now();
+            let assumed = assume_role(&credentials, &options).await?;
+            let role = parse_role_arn(role_arn)?;
+
+            args.timing("sts", profile_name, started);
+
+            sso_profile.sso_account_id = Some(role.account_id);
+            sso_profile.sso_role_name = Some(role.role_name);
+
+            assumed
+        }
+        None => credentials,
+    };
+
+    warn_if_expiring(args, profile_name, &credentials);
+
+    let account_alias = match args.print_account_alias {
+        true => {
+            let started = Instant::now();
+            let account_alias = lookup_account_alias(args, &sso_profile, &credentials).await?;
+
+            args.timing("iam", profile_name, started);
+
+            account_alias
+        }
+        false => None,
+    };
+
+    args.timing("total", profile_name, obtaining);
+
+    Ok(Obtained {
+        sso_profile,
+        credentials,
+        token_expires_at: encoded,
+        account_alias,
+    })
+}
+
+/// The alias of the account the credentials were issued for, from the cache if looked up within the last day. Failing
+/// to look it up is only logged, as the alias is just a convenience, unless `--strict` is given.
+async fn lookup_account_alias(
+    args: &Args,
+    sso_profile: &SsoProfile,
+    credentials: &SsoCredentials,
+) -> Result<Option<String>> {
+    let cache_file = alias_cache_file(args.cache_dir()?.as_path(), sso_profile.account_id()?);
+
+    if !args.no_cache {
+        if let Some(cached) = load_cached_alias(cache_file.as_path()).await {
+            log::debug!("Reusing cached account alias.");
+            return Ok(cached);
+        }
+    }
+
+    if args.offline {
+        return match args.strict {
+            true => Err(anyhow!(
+                "--strict: no account alias is cached, and --offline prevents looking it up"
+            )),
+            false => Ok(None),
+        };
+    }
+
+    let options = AliasOptions {
+        region: args
+            .region(sso_profile)
+            .unwrap_or(sso_profile.sso_region.as_str())
+            .into(),
+        endpoint_url: args.iam_endpoint_url.clone(),
+        variant: args.endpoint_variant(),
+    };
+
+    let alias = match account_alias(credentials, &options).await {
+        Ok(alias) => alias,
+        Err(e) if args.strict => return Err(e.into()),
+        Err(Error::IamAccessDenied(_)) => {
+            log::debug!("The credentials aren't permitted to list the account's aliases.");
+            return Ok(None);
+        }
+        Err(e) => {
+            log::warn!("Unable to look up the account alias: {}", e);
+            return Ok(None);
+        }
+    };
+
+    if !args.no_cache {
+        if let Err(e) = save_cached_alias(cache_file.as_path(), alias.as_deref()).await {
+            log::warn!("Unable to cache the account alias: {:?}", e);
+        }
+    }
+
+    Ok(alias)
+}
+
+/// Warn if the local clock was found to be far from AWS's, as it would explain tokens and credentials wrongly appearing
+/// to be expired, or valid when AWS rejects them as expired.
+fn warn_if_clock_skewed() {
+    let skew = match observed_skew() {
+        Some(skew) if skew.abs() > SKEW_THRESHOLD => skew,
+        _ => return,
+    };
+
+    log::warn!(
+        "The local clock is {} seconds {} AWS's, so SSO tokens and credentials may wrongly appear to be expired or \
+         valid. Synchronise it, e.g. using NTP.",
+        skew.whole_seconds().abs(),
+        if skew.is_positive() { "ahead of" } else { "behind" }
+    );
+}
+
+/// Warn if the credentials expire within `--warn-before` minutes, so that there's time to log in again before they do.
+fn warn_if_expiring(args: &Args, profile_name: &str, credentials: &SsoCredentials) {
+    let remaining = credentials.expires_at - OffsetDateTime::now_utc();
+
+    if args.warn_before == 0 || remaining > time::Duration::minutes(args.warn_before.into()) {
+        return;
+    }
+
+    log::warn!(
+        "Credentials for profile '{}' expire in {} minute(s), at {}.",
+        profile_name,
+        remaining.whole_minutes().max(0),
+        credentials.expires_at.format(&Rfc3339).unwrap_or_default()
+    );
+}
+
+/// Load the profile's cached SSO token, refreshing it or logging in as the arguments allow, failing with a hint to log in
+/// if no unexpired token can be found.
+async fn load_valid_token(
+    args: &Args,
+    profile_name: &str,
+    sso_profile: &SsoProfile,
+) -> Result<CachedSsoToken> {
+    // see if there is a cached SSO token available in the cached tokens directories
+    if args.debug_cache {
+        debug_cache(args, sso_profile).await?;
+    }
+
+    let mut cached_sso_token = args.cached_token(sso_profile).await?;
+
+    // if requested, log in when there is no usable token and then look for the token again
+    let expiry_cutoff = args.expiry_cutoff();
+
+    let is_expired = |token: &CachedSsoToken| expiry_cutoff > token.expires_at;
+
+    // an expired token can usually be refreshed without logging in again
+    if let Some(token) = cached_sso_token
+        .as_ref()
+        .filter(|token| !args.offline && !args.dry_run && is_expired(token))
+    {
+        if let Some(refreshed) = refresh_expired_token(args, token).await {
+            cached_sso_token = Some(refreshed);
+        }
+    }
+
+    if args.login && cached_sso_token.as_ref().is_none_or(is_expired) {
+        log::info!("Cached SSO token is missing or expired, logging in.");
+
+        run_sso_login(profile_name).await?;
+
+        cached_sso_token = args.cached_token(sso_profile).await?;
+    }
+
+    let cached_sso_token = match cached_sso_token {
+        Some(token) => token,
+        None => {
+            log::info!("Run 'aws --profile {} sso login' to log in.", profile_name);
+
+            return Err(args.missing_token(profile_name, sso_profile).await?.into());
+        }
+    };
+
+    log::debug!("Loaded cached SSO token.");
+
+    if expiry_cutoff > cached_sso_token.expires_at {
+        log::info!(
+            "Run 'aws --profile {} sso login' to refresh credentials.",
+            profile_name
+        );
+        return Err(Error::CacheTokenExpired(cached_sso_token.expires_at.format(&Rfc3339)?).into());
+    }
+
+    Ok(cached_sso_token)
+}
+
+/// Describe how the profile's SSO token is looked up in the cache directories on standard error, for `--debug-cache`.
+async fn debug_cache(args: &Args, sso_profile: &SsoProfile) -> Result<()> {
+    let exists = |path: &Path| match path.exists() {
+        true => "exists",
+        false => "missing",
+    };
+
+    let cache_dirs = args.token_cache_dirs()?;
+
+    if args.token_source == TokenBackend::Keyring {
+        for cache_key in cache_keys(sso_profile) {
+            eprintln!("keyring service '{}', key '{}'", TOKEN_SERVICE, cache_key);
+        }
+    }
+
+    for cache_dir in cache_dirs
+        .iter()
+        .filter(|_| args.token_source == TokenBackend::File)
+    {
+        eprintln!(
+            "cache directory: {} ({})",
+            cache_dir.display(),
+            exists(cache_dir)
+        );
+
+        for (cache_key, cache_file) in cache_files(sso_profile, cache_dir) {
+            eprintln!(
+                "cache key '{}': {} ({})",
+                cache_key,
+                cache_file.display(),
+                exists(cache_file.as_path())
+            );
+        }
+    }
+
+    // only the token's location and expiry are printed, never the token itself
+    match args.cached_token(sso_profile).await? {
+        Some(token) => eprintln!(
+            "token found: {}, expires at {}",
+            match (token.cache_file.as_deref(), args.token_source) {
+                (Some(cache_file), _) => cache_file.display().to_string(),
+                (None, TokenBackend::Keyring) => "in the keyring".into(),
+                (None, TokenBackend::File) => cache_dirs[0].display().to_string(),
+            },
+            token.expires_at.format(&Rfc3339).unwrap_or_default()
+        ),
+        None if args.token_source == TokenBackend::Keyring => {
+            eprintln!("token found: none in the keyring")
+        }
+        None => eprintln!(
+            "token found: none, searched the cache directories for start url {}",
+            sso_profile.sso_start_url
+        ),
+    }
+
+    Ok(())
+}
+
+/// Refresh the expired token using its cached refresh token, if it has one, logging why it couldn't be refreshed.
+async fn refresh_expired_token(args: &Args, token: &CachedSsoToken) -> Option<CachedSsoToken> {
+    let endpoint_url = match args.oidc_endpoint_url.clone() {
+        Some(endpoint_url) => Some(endpoint_url),
+        None => match args
+            .endpoint_variant()
+            .endpoint_url("oidc", token.region.as_str())
+        {
+            Ok(endpoint_url) => endpoint_url,
+            Err(e) => {
+                log::warn!("Unable to refresh the expired SSO token: {}", e);
+                return None;
+            }
+        },
+    };
+
+    match refresh_cached_token(token, endpoint_url.as_deref()).await {
+        Ok(Some(refreshed)) => {
+            log::debug!("Refreshed the expired SSO token.");
+            Some(refreshed)
+        }
+        Ok(None) => {
+            log::debug!("Cached SSO token has no refresh token, so can't be refreshed.");
+            None
+        }
+        Err(e) => {
+            log::warn!("Unable to refresh the expired SSO token: {}", e);
+            None
+        }
+    }
+}
+
+/// Run `aws --profile <profile_name> sso login` and wait for it to finish.
+///
+/// The login command's standard error is passed through so that its browser prompt is visible, and its standard output
+/// is redirected to standard error so that it can't be mistaken for credentials.
+async fn run_sso_login(profile_name: &str) -> Result<()> {
+    let program = std::env::var(AWS_CLI_ENV_VAR).unwrap_or_else(|_| "aws".into());
+
+    log::debug!("Running '{} --profile {} sso login'", program, profile_name);
+
+    let status = tokio::process::Command::new(program.as_str())
+        .args(["--profile", profile_name, "sso", "login"])
+        .stdin(Stdio::inherit())
+        .stdout(std::io::stderr())
+        .stderr(Stdio::inherit())
+        .status()
+        .await
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => anyhow!(
+                "unable to find '{}' on the PATH; install the AWS CLI or set {} to its location",
+                program,
+                AWS_CLI_ENV_VAR
+            ),
+            _ => anyhow!("unable to run '{}': {}", program, e),
+        })?;
+
+    if !status.success() {
+        return Err(anyhow!("'{} sso login' failed: {}", program, status));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,669 @@
+//! Rendering of credentials in the syntax of various shells and tools.
+
+use serde::Serialize;
+
+use std::str::FromStr;
+
+use zeroize::Zeroize;
+
+use crate::error::{Error, Result};
+use crate::{SsoCredentials, SsoProfile};
+
+/// The shell syntax used when printing credentials to standard output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Bash,
+    Fish,
+    PowerShell,
+    Cmd,
+    Dotenv,
+    Json,
+    JsonAws,
+    /// Exactly the credential variables that `aws configure export-credentials --format env` exports, in its order.
+    Env,
+    /// As [`OutputFormat::Env`], without the `export` keyword, like `--format env-no-export`.
+    EnvNoExport,
+    /// The JSON of [`OutputFormat::Json`] on one line, base64 encoded, for passing through environment variables or
+    /// HTTP headers without any quoting.
+    Base64Json,
+}
+
+/// The credentials issued for a profile, along with everything else printed with them.
+#[derive(Debug)]
+pub struct Exports<'a> {
+    pub profile: &'a SsoProfile,
+    pub credentials: &'a SsoCredentials,
+    /// When the SSO token used to obtain the credentials expires, noted in a comment.
+    pub token_expires_at: &'a str,
+    /// The names of the variables holding when the credentials expire, usually just [`EXPIRATION_VARIABLE`].
+    pub expiration_variables: &'a [String],
+    /// The region exported as both `AWS_DEFAULT_REGION` and `AWS_REGION`, if any.
+    pub region: Option<&'a str>,
+    /// The alias of the account the credentials were issued for, exported as `AWS_ACCOUNT_ALIAS` if looked up.
+    pub account_alias: Option<&'a str>,
+    /// Prepended to the name of every exported variable, to namespace the credentials for a tool wrapping several.
+    pub prefix: Option<&'a str>,
+    /// Appended to the name of every exported variable, to tell apart the credentials of several profiles.
+    pub suffix: Option<&'a str>,
+    /// The profile's properties to export as variables, after the credentials and region.
+    pub passthrough: &'a [Passthrough],
+    /// Whether to precede the assignments with comments noting the role's ARN and when the SSO token expires.
+    pub comments: bool,
+}
+
+/// A property of the profile exported as a variable of the given name, e.g. `cli_pager` as `AWS_PAGER`, parsed from
+/// `key=VARIABLE`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Passthrough {
+    pub key: String,
+    pub variable: String,
+}
+
+impl FromStr for Passthrough {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((key, variable))
+                if !key.trim().is_empty()
+                    && !variable.is_empty()
+                    && variable
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+            {
+                Ok(Passthrough {
+                    key: key.trim().into(),
+                    variable: variable.into(),
+                })
+            }
+            _ => Err(Error::Parse(format!(
+                "passthrough '{}', expected key=VARIABLE",
+                s
+            ))),
+        }
+    }
+}
+
+/// JSON representation of [`SsoCredentials`] along with the profile they were issued for, used by
+/// [`OutputFormat::Json`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonCredentials<'a> {
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    session_token: &'a str,
+    expires_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<&'a str>,
+    account_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_alias: Option<&'a str>,
+    role_name: &'a str,
+    profile_name: &'a str,
+}
+
+/// Credentials as printed for the AWS `credential_process` configuration setting, and by [`OutputFormat::JsonAws`].
+///
+/// See the [AWS documentation](https://docs.aws.amazon.com/cli/latest/userguide/cli-configure-sourcing-external.html)
+/// for the expected schema.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CredentialProcessOutput<'a> {
+    version: u8,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    session_token: &'a str,
+    expiration: String,
+}
+
+impl<'a> CredentialProcessOutput<'a> {
+    /// Describe the given credentials, borrowing rather than copying the secrets.
+    pub fn new(credentials: &'a SsoCredentials) -> Result<Self> {
+        Ok(CredentialProcessOutput {
+            version: 1,
+            access_key_id: credentials.access_key_id.as_str(),
+            secret_access_key: credentials.secret_access_key.as_str(),
+            session_token: credentials.session_token.as_str(),
+            expiration: credentials.expiration()?,
+        })
+    }
+}
+
+impl OutputFormat {
+    pub const VARIANTS: &'static [&'static str] = &[
+        "bash",
+        "fish",
+        "powershell",
+        "cmd",
+        "dotenv",
+        "json",
+        "json-aws",
+        "env",
+        "env-no-export",
+        "base64-json",
+    ];
+
+    /// Whether the format exports nothing but the credentials, without comments, the region, or any other variables,
+    /// to match `aws configure export-credentials`.
+    pub fn is_credentials_only(&self) -> bool {
+        matches!(self, OutputFormat::Env | OutputFormat::EnvNoExport)
+    }
+
+    /// Render the credentials issued for a profile in this format, preceded by comments noting the role they were issued
+    /// for and when the SSO token expires, unless comments are turned off.
+    ///
+    /// Some formats differ:
+    ///
+    /// - JSON output describes the credentials along with the account, role, and profile they were issued for. It
+    ///   ignores the prefix and suffix.
+    /// - Base64 JSON output is the same JSON, compacted and base64 encoded.
+    /// - AWS JSON output is exactly what `credential_process` expects. It ignores the prefix and suffix too.
+    /// - The `env` formats export only the credentials, never with comments, as `aws configure export-credentials`
+    ///   does.
+    pub fn render(&self, exports: &Exports) -> Result<String> {
+        let Exports {
+            profile,
+            credentials,
+            token_expires_at,
+            expiration_variables,
+            region,
+            account_alias,
+            prefix,
+            suffix,
+            passthrough,
+            comments,
+        } = *exports;
+
+        if let OutputFormat::Json | OutputFormat::Base64Json = self {
+            let json = JsonCredentials {
+                access_key_id: credentials.access_key_id.as_str(),
+                secret_access_key: credentials.secret_access_key.as_str(),
+                session_token: credentials.session_token.as_str(),
+                expires_at: credentials.expiration()?,
+                region,
+                account_id: profile.account_id()?,
+                account_alias,
+                role_name: profile.role_name()?,
+                profile_name: profile.profile_name.as_str(),
+            };
+
+            let values_len = json.access_key_id.len()
+                + json.secret_access_key.len()
+                + json.session_token.len()
+                + json.expires_at.len()
+                + json.region.map_or(0, str::len)
+                + json.account_id.len()
+                + json.account_alias.map_or(0, str::len)
+                + json.role_name.len()
+                + json.profile_name.len();
+
+            if let OutputFormat::Base64Json = self {
+                let mut json = to_json(&json, values_len, false)?;
+
+                // the encoding is sized exactly, so is copied into a buffer with room for the line break
+                let mut encoded = aws_smithy_types::base64::encode(json.trim_end());
+                let mut output = String::with_capacity(encoded.len() + 1);
+
+                output.push_str(encoded.as_str());
+                output.push('\n');
+
+                json.zeroize();
+                encoded.zeroize();
+
+                return Ok(output);
+            }
+
+            return to_json(&json, values_len, true);
+        }
+
+        if let OutputFormat::JsonAws = self {
+            let json = CredentialProcessOutput::new(credentials)?;
+            let values_len = json.access_key_id.len()
+                + json.secret_access_key.len()
+                + json.session_token.len()
+                + json.expiration.len();
+
+            return to_json(&json, values_len, false);
+        }
+
+        let credentials_only = self.is_credentials_only();
+        let comments = comments && !credentials_only;
+
+        let expiration = credentials.expiration()?;
+        let role_arn = match comments {
+            true => profile.role_arn()?,
+            false => String::new(),
+        };
+
+        let values = [
+            credentials.access_key_id.as_str(),
+            credentials.secret_access_key.as_str(),
+            credentials.session_token.as_str(),
+        ];
+
+        let mut variables: Vec<(&str, &str)> =
+            CREDENTIAL_VARIABLES.iter().copied().zip(values).collect();
+
+        variables.extend(
+            expiration_variables
+                .iter()
+                .map(|name| (name.as_str(), expiration.as_str())),
+        );
+
+        if let Some(region) = region.filter(|_| !credentials_only) {
+            variables.extend(REGION_VARIABLES.iter().map(|name| (*name, region)));
+        }
+
+        if let Some(output) = profile.output.as_deref().filter(|_| !credentials_only) {
+            variables.push((OUTPUT_VARIABLE, output));
+        }
+
+        if let Some(account_alias) = account_alias.filter(|_| !credentials_only) {
+            variables.push((ACCOUNT_ALIAS_VARIABLE, account_alias));
+        }
+
+        variables.extend(passthrough.iter().filter(|_| !credentials_only).filter_map(
+            |passthrough| {
+                profile
+                    .properties
+                    .get(passthrough.key.as_str())
+                    .map(|value| (passthrough.variable.as_str(), value.as_str()))
+            },
+        ));
+
+        // the secrets are copied straight into an output buffer large enough to never be reallocated, as the caller can
+        // only zeroize the final buffer and not any intermediate copies; quoting can at worst quadruple a value, as a
+        // single quote becomes `'\''` in the POSIX shells
+        let capacity = variables
+            .iter()
+            .map(|(name, value)| name.len() + 4 * value.len() + 16)
+            .sum::<usize>()
+            + variables.len()
+                * (prefix.map_or(0, |prefix| prefix.len() + 1)
+                    + suffix.map_or(0, |suffix| suffix.len() + 1))
+            + token_expires_at.len()
+            + role_arn.len()
+            + account_alias.map_or(0, |account_alias| account_alias.len() + 16)
+            + 32;
+
+        let mut output = String::with_capacity(capacity);
+
+        if comments {
+            self.comment(&mut output, format!("role arn: {}", role_arn).as_str());
+
+            if let Some(account_alias) = account_alias {
+                self.comment(
+                    &mut output,
+                    format!("account alias: {}", account_alias).as_str(),
+                );
+            }
+
+            self.comment(
+                &mut output,
+                format!("expires at {}", token_expires_at).as_str(),
+            );
+        }
+
+        for (name, value) in variables {
+            self.assignment(
+                &mut output,
+                variable_name(prefix, name, suffix).as_str(),
+                value,
+            );
+        }
+
+        Ok(output)
+    }
+
+    /// Render the commands which remove the named variables from the environment, as the inverse of [`render`].
+    ///
+    /// [`render`]: OutputFormat::render
+    pub fn render_unset(&self, names: &[String]) -> Result<String> {
+        let mut output = String::new();
+
+        match self {
+            OutputFormat::Bash | OutputFormat::Env | OutputFormat::EnvNoExport => {
+                output.push_str("unset ");
+                output.push_str(names.join(" ").as_str());
+                output.push('\n');
+            }
+            OutputFormat::Fish => names
+                .iter()
+                .for_each(|name| output.push_str(format!("set -e {}\n", name).as_str())),
+            OutputFormat::PowerShell => names.iter().for_each(|name| {
+                output.push_str(
+                    format!("Remove-Item Env:{} -ErrorAction SilentlyContinue\n", name).as_str(),
+                )
+            }),
+            OutputFormat::Cmd => names
+                .iter()
+                .for_each(|name| output.push_str(format!("set {}=\n", name).as_str())),
+            OutputFormat::Dotenv
+            | OutputFormat::Json
+            | OutputFormat::JsonAws
+            | OutputFormat::Base64Json => {
+                return Err(Error::Format(
+                    "unset commands for anything but a shell".into(),
+                ))
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn comment(&self, output: &mut String, text: &str) {
+        match self {
+            OutputFormat::Cmd => output.push_str("REM "),
+            _ => output.push_str("# "),
+        }
+
+        output.push_str(text);
+        output.push('\n');
+    }
+
+    fn assignment(&self, output: &mut String, name: &str, value: &str) {
+        let (prefix, separator) = match self {
+            OutputFormat::Bash | OutputFormat::Env => ("export ", "="),
+            OutputFormat::Fish => ("set -gx ", " "),
+            OutputFormat::PowerShell => ("$env:", " = "),
+            OutputFormat::Cmd => ("set ", "="),
+            OutputFormat::Dotenv | OutputFormat::EnvNoExport => ("", "="),
+            OutputFormat::Json | OutputFormat::JsonAws | OutputFormat::Base64Json => {
+                unreachable!("json output does not consist of assignments")
+            }
+        };
+
+        output.push_str(prefix);
+        output.push_str(name);
+        output.push_str(separator);
+
+        match self {
+            OutputFormat::Bash | OutputFormat::Env | OutputFormat::EnvNoExport => {
+                push_posix_value(output, value)
+            }
+            OutputFormat::Fish => push_fish_value(output, value),
+            OutputFormat::Dotenv => push_dotenv_value(output, value),
+            OutputFormat::PowerShell => push_powershell_value(output, value),
+            _ => output.push_str(value),
+        }
+
+        output.push('\n');
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bash" => Ok(OutputFormat::Bash),
+            "fish" => Ok(OutputFormat::Fish),
+            "powershell" => Ok(OutputFormat::PowerShell),
+            "cmd" => Ok(OutputFormat::Cmd),
+            "dotenv" => Ok(OutputFormat::Dotenv),
+            "json" => Ok(OutputFormat::Json),
+            "json-aws" => Ok(OutputFormat::JsonAws),
+            "env" => Ok(OutputFormat::Env),
+            "env-no-export" => Ok(OutputFormat::EnvNoExport),
+            "base64-json" => Ok(OutputFormat::Base64Json),
+            _ => Err(Error::Parse(format!("output format '{}'", s))),
+        }
+    }
+}
+
+/// The variables holding the credentials, exported for every profile in this order.
+const CREDENTIAL_VARIABLES: [&str; 3] = [
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+];
+
+/// The variable holding when the credentials expire, exported after the credentials unless other names are given.
+pub const EXPIRATION_VARIABLE: &str = "AWS_CREDENTIAL_EXPIRATION";
+
+/// The variables holding the region, exported after the credentials when there is one.
+const REGION_VARIABLES: [&str; 2] = ["AWS_DEFAULT_REGION", "AWS_REGION"];
+
+/// The variable holding the AWS CLI's default output format, exported after the region when the profile sets `output`.
+pub const OUTPUT_VARIABLE: &str = "AWS_DEFAULT_OUTPUT";
+
+/// The variable holding the alias of the account, exported after the output format when it's looked up.
+pub const ACCOUNT_ALIAS_VARIABLE: &str = "AWS_ACCOUNT_ALIAS";
+
+/// The names of the variables [`OutputFormat::render`] exports, given the names the expiration is exported under,
+/// whether a region is exported, the names of any further variables exported after it, and the prefix and suffix.
+pub fn variable_names(
+    expiration: &[String],
+    region: bool,
+    extra: &[&str],
+    prefix: Option<&str>,
+    suffix: Option<&str>,
+) -> Vec<String> {
+    let region_variables: &[&str] = if region { &REGION_VARIABLES } else { &[] };
+
+    CREDENTIAL_VARIABLES
+        .iter()
+        .copied()
+        .chain(expiration.iter().map(String::as_str))
+        .chain(region_variables.iter().copied())
+        .chain(extra.iter().copied())
+        .map(|name| variable_name(prefix, name, suffix))
+        .collect()
+}
+
+/// The name of the variable, with the prefix prepended and the suffix appended, each joined by an underscore, if there
+/// are any.
+fn variable_name(prefix: Option<&str>, name: &str, suffix: Option<&str>) -> String {
+    let mut variable_name = String::new();
+
+    if let Some(prefix) = prefix {
+        variable_name.push_str(prefix);
+        variable_name.push('_');
+    }
+
+    variable_name.push_str(name);
+
+    if let Some(suffix) = suffix {
+        variable_name.push('_');
+        variable_name.push_str(suffix);
+    }
+
+    variable_name
+}
+
+/// The suffix for the variables of the given profile: its name, uppercased, with anything other than letters and digits
+/// replaced by underscores, e.g. `MY_PROD` for `my-prod`.
+pub fn variable_suffix(profile_name: &str) -> String {
+    profile_name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// The most that JSON output adds to the values it holds, in its keys, punctuation, and indentation.
+const JSON_OVERHEAD: usize = 512;
+
+/// Serialize the value as JSON followed by a line break, into a buffer large enough to never be reallocated, as with
+/// the other formats. Escaping can at worst make each byte of the values, of the given total length, six bytes long.
+fn to_json<T: Serialize>(value: &T, values_len: usize, pretty: bool) -> Result<String> {
+    let mut output = Vec::with_capacity(6 * values_len + JSON_OVERHEAD);
+
+    let serialized = match pretty {
+        true => serde_json::to_writer_pretty(&mut output, value),
+        false => serde_json::to_writer(&mut output, value),
+    };
+
+    if let Err(e) = serialized {
+        output.zeroize();
+        return Err(Error::Format(format!("credentials: {}", e)));
+    }
+
+    output.push(b'\n');
+
+    // serde_json only ever writes UTF-8
+    String::from_utf8(output).map_err(|e| {
+        e.into_bytes().zeroize();
+        Error::Format("credentials: not UTF-8".into())
+    })
+}
+
+/// Whether the value can be left unquoted in any of the formats, consisting only of characters that no shell or dotenv
+/// parser interprets.
+fn is_plain(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "+-./:=@_,".contains(c))
+}
+
+/// Append a value for bash or zsh, single-quoted if it isn't plain, so that the output is always safe to `eval`. Single
+/// quotes can't be escaped within single quotes, so each one closes the string, is escaped, and reopens it.
+fn push_posix_value(output: &mut String, value: &str) {
+    if is_plain(value) {
+        output.push_str(value);
+        return;
+    }
+
+    output.push('\'');
+
+    for c in value.chars() {
+        match c {
+            '\'' => output.push_str("'\\''"),
+            c => output.push(c),
+        }
+    }
+
+    output.push('\'');
+}
+
+/// Append a value for fish, single-quoted if it isn't plain, escaping the only characters fish interprets within single
+/// quotes: backslashes and single quotes.
+fn push_fish_value(output: &mut String, value: &str) {
+    if is_plain(value) {
+        output.push_str(value);
+        return;
+    }
+
+    output.push('\'');
+
+    for c in value.chars() {
+        if c == '\\' || c == '\'' {
+            output.push('\\');
+        }
+
+        output.push(c);
+    }
+
+    output.push('\'');
+}
+
+/// Append a value as a double-quoted PowerShell string, escaping the characters it would otherwise interpret, including
+/// the typographic double quotes that PowerShell treats as ordinary ones.
+fn push_powershell_value(output: &mut String, value: &str) {
+    output.push('"');
+
+    for c in value.chars() {
+        if matches!(c, '`' | '"' | '$' | '\u{201c}' | '\u{201d}' | '\u{201e}') {
+            output.push('`');
+        }
+
+        output.push(c);
+    }
+
+    output.push('"');
+}
+
+/// Append a value for a dotenv file, quoted if it contains anything other than the characters of an unquoted word.
+fn push_dotenv_value(output: &mut String, value: &str) {
+    if is_plain(value) {
+        output.push_str(value);
+        return;
+    }
+
+    output.push('"');
+
+    for c in value.chars() {
+        if c == '\\' || c == '"' {
+            output.push('\\');
+        }
+
+        output.push(c);
+    }
+
+    output.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use time::OffsetDateTime;
+
+    #[test]
+    fn escapes_quotes_and_backticks_in_powershell_tokens() {
+        let profile = SsoProfile {
+            profile_name: "dev".into(),
+            region: None,
+            output: None,
+            sso_account_id: Some("123456789012".into()),
+            sso_region: "us-east-1".into(),
+            sso_role_name: Some("Developer".into()),
+            sso_session: None,
+            sso_start_url: "https://example.awsapps.com/start".into(),
+            properties: BTreeMap::new(),
+        };
+        let credentials = SsoCredentials {
+            access_key_id: "ASIAEXAMPLE".into(),
+            secret_access_key: "secret".into(),
+            session_token: r#"to"k`en"#.into(),
+            expires_at: OffsetDateTime::UNIX_EPOCH,
+        };
+
+        let output = OutputFormat::PowerShell
+            .render(&Exports {
+                profile: &profile,
+                credentials: &credentials,
+                token_expires_at: "2030-01-01T00:00:00Z",
+                expiration_variables: &[EXPIRATION_VARIABLE.into()],
+                region: None,
+                account_alias: None,
+                prefix: None,
+                suffix: None,
+                passthrough: &[],
+                comments: false,
+            })
+            .unwrap();
+
+        assert!(
+            output.contains("$env:AWS_SESSION_TOKEN = \"to`\"k``en\"\n"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn never_grows_json_output_with_escaped_secrets() {
+        let session_token = "\u{1}".repeat(1024);
+        let json = JsonCredentials {
+            access_key_id: "ASIAEXAMPLE",
+            secret_access_key: "\"\\",
+            session_token: session_token.as_str(),
+            expires_at: "2030-01-01T00:00:00Z".into(),
+            region: Some("us-east-1"),
+            account_id: "123456789012",
+            account_alias: Some("example"),
+            role_name: "Developer",
+            profile_name: "dev",
+        };
+        let values_len = 11 + 2 + session_token.len() + 20 + 9 + 12 + 7 + 9 + 3;
+
+        let output = to_json(&json, values_len, true).unwrap();
+
+        assert!(output.contains(&"\\u0001".repeat(1024)), "{}", output);
+        assert_eq!(output.capacity(), 6 * values_len + JSON_OVERHEAD);
+    }
+}
@@ -0,0 +1,514 @@
+//! Loading of SSO profiles from the AWS configuration files.
+
+use aws_config::profile::{Profile, ProfileSet};
+
+use aws_types::os_shim_internal::{Env, Fs};
+
+use http::Uri;
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::ErrorKind;
+use std::path::Path;
+
+use zeroize::Zeroize;
+
+use crate::error::{Error, Result};
+use crate::files::{aws_config_file, aws_credentials_file};
+use crate::ini::{ini_key, ini_section_name, ini_sections};
+
+/// Representation of an SSO profile's configuration within `~/.aws/config` or `~/.aws/credentials`.
+///
+/// This struct contains all the necessary fields to facilitate single-sign-on for an AWS account with a role.
+#[derive(Debug)]
+pub struct SsoProfile {
+    pub profile_name: String,
+    /// The region the profile operates in, if set. Fetching credentials only needs the `sso_region`.
+    pub region: Option<String>,
+    /// The default output format of the AWS CLI set by the profile's `output`, if any.
+    pub output: Option<String>,
+    /// The account to fetch credentials for, which only fetching credentials and listing roles need.
+    pub sso_account_id: Option<String>,
+    pub sso_region: String,
+    /// The role to fetch credentials for, which only fetching credentials needs.
+    pub sso_role_name: Option<String>,
+    /// The name of the `[sso-session NAME]` section the profile refers to, if any.
+    pub sso_session: Option<String>,
+    pub sso_start_url: String,
+    /// The values of any further properties asked for when loading the profile and set by it, by name.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl SsoProfile {
+    /// The profile's `sso_account_id`, failing if it has none.
+    pub fn account_id(&self) -> Result<&str> {
+        self.sso_account_id
+            .as_deref()
+            .ok_or_else(|| Error::MissingProfileKey("sso_account_id".into()))
+    }
+
+    /// The profile's `sso_role_name`, failing if it has none.
+    pub fn role_name(&self) -> Result<&str> {
+        self.sso_role_name
+            .as_deref()
+            .ok_or_else(|| Error::MissingProfileKey("sso_role_name".into()))
+    }
+
+    /// The ARN of the profile's role, `arn:<partition>:iam::<account>:role/<role>`, in the partition of its
+    /// `sso_region`. Roles provisioned by SSO actually live under a generated path and name, so this identifies the role
+    /// to a reader rather than to IAM.
+    pub fn role_arn(&self) -> Result<String> {
+        let partition = match self.sso_region.as_str() {
+            region if region.starts_with("cn-") => "aws-cn",
+            region if region.starts_with("us-gov-") => "aws-us-gov",
+            region if region.starts_with("us-isob-") => "aws-iso-b",
+            region if region.starts_with("us-iso-") => "aws-iso",
+            _ => "aws",
+        };
+
+        Ok(format!(
+            "arn:{}:iam::{}:role/{}",
+            partition,
+            self.account_id()?,
+            self.role_name()?
+        ))
+    }
+}
+
+/// Load the SSO profile with the given name from the AWS configuration files.
+///
+/// Keys the profile doesn't set itself are looked up along its chain of `source_profile` references, so that derived
+/// profiles can share the SSO settings of a base profile.
+pub async fn get_sso_profile<S: AsRef<str>>(profile_name: S) -> Result<SsoProfile> {
+    get_sso_profile_with_properties(profile_name, &[]).await
+}
+
+/// Load the SSO profile with the given name like [`get_sso_profile`], also reading the values of the named properties
+/// into its `properties`, e.g. to pass them through to the environment.
+pub async fn get_sso_profile_with_properties<S: AsRef<str>>(
+    profile_name: S,
+    keys: &[&str],
+) -> Result<SsoProfile> {
+    // each file is read just once, with the sdk parsing the profiles from memory and the sso-sessions parsed here
+    let config = read_optional_file(aws_config_file()?.as_path()).await?;
+    let mut credentials = read_optional_file(aws_credentials_file()?.as_path()).await?;
+
+    let profiles = parse_profiles(config.as_str(), credentials.as_str()).await;
+
+    // only this copy ever holds the static credentials, as they're stripped before the sdk parses the file
+    credentials.zeroize();
+
+    let profiles = profiles?;
+
+    // get the profile with the given name
+    //
+    // NOTE the sdk does not allow you to list profiles, which is an interesting choice, you have to _know_ what
+    //      profile you're looking for
+    if let Some(profile) = profiles.get_profile(&sdk_profile_name(profile_name.as_ref())) {
+        // keys missing from the profile are inherited from its chain of source profiles
+        let chain = source_profile_chain(&profiles, profile_name.as_ref(), profile)?;
+        let profile = Inherited(chain);
+
+        // newer configurations keep the start url and region in a separate sso-session section
+        let sso_session = match profile.get("sso_session") {
+            Some(session_name) => Some(sso_session(config.as_str(), profile_value(session_name))?),
+            None => None,
+        };
+
+        // prefer the values from the sso-session, falling back to those set directly on the profile
+        let sso_property = |key: &str| -> Result<String> {
+            sso_session
+                .as_ref()
+                .and_then(|session| session.get(key).map(String::as_str))
+                .or_else(|| profile.get(key))
+                .map(|value| profile_value(value).into())
+                .ok_or_else(|| Error::MissingProfileKey(key.into()))
+        };
+
+        let property = |key: &str| -> Result<String> {
+            profile
+                .get(key)
+                .map(|value| profile_value(value).into())
+                .ok_or_else(|| Error::MissingProfileKey(key.into()))
+        };
+
+        // like the AWS CLI, settings the profile lacks are taken from the default profile, but never its sso keys, which
+        // would otherwise fetch credentials for whatever account and role the default profile names
+        let default_profile = profiles.get_profile("default");
+
+        let setting = |key: &str| -> Option<String> {
+            property(key)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .or_else(|| {
+                    default_profile
+                        .and_then(|default| default.get(key))
+                        .map(|value| profile_value(value).to_string())
+                        .filter(|value| !value.is_empty())
+                })
+        };
+
+        // only fetching credentials needs the account and role, so discovering them needn't
+        let sso_account_id = property("sso_account_id").ok();
+
+        if let Some(sso_account_id) = sso_account_id
+            .as_deref()
+            .filter(|sso_account_id| !is_account_id(sso_account_id))
+        {
+            return Err(Error::InvalidProfileValue {
+                profile: profile_name.as_ref().into(),
+                key: "sso_account_id",
+                reason: format!("'{}' is not a 12-digit account id", sso_account_id),
+            });
+        }
+
+        // a mistyped start url would otherwise only surface as a cache miss, as the cache is keyed by it
+        let sso_start_url = sso_property("sso_start_url")?;
+
+        if !is_https_url(sso_start_url.as_str()) {
+            return Err(Error::InvalidProfileValue {
+                profile: profile_name.as_ref().into(),
+                key: "sso_start_url",
+                reason: format!("'{}' is not a valid https URL", sso_start_url),
+            });
+        }
+
+        // extract all the properties, converting them to errors if not present
+        Ok(SsoProfile {
+            profile_name: profile_name.as_ref().into(),
+            region: setting("region"),
+            output: setting("output"),
+            sso_account_id,
+            sso_region: sso_property("sso_region")?,
+            sso_role_name: property("sso_role_name").ok(),
+            sso_session: property("sso_session").ok(),
+            sso_start_url,
+            properties: keys
+                .iter()
+                .filter_map(|key| Some((key.to_string(), property(key).ok()?)))
+                .collect(),
+        })
+    } else if std::env::var_os(START_URL_ENV_VAR).is_some() {
+        // without a profile on disk, the environment may well describe one
+        log::debug!(
+            "Profile '{}' not found, reading it from the environment.",
+            profile_name.as_ref()
+        );
+
+        sso_profile_from_env(profile_name.as_ref())
+    } else {
+        // the profile was not found
+        Err(Error::ProfileNotFound(profile_name.as_ref().into()))
+    }
+}
+
+/// The environment variable holding the SSO start url of a profile read from the environment, whose presence makes
+/// [`get_sso_profile`] fall back to the environment for profiles not found on disk.
+const START_URL_ENV_VAR: &str = "AWS_SSO_START_URL";
+
+/// Build an SSO profile with the given name from the `AWS_SSO_START_URL`, `AWS_SSO_REGION`, `AWS_SSO_ACCOUNT_ID`,
+/// `AWS_SSO_ROLE_NAME`, and optionally `AWS_REGION` environment variables, for environments without any AWS
+/// configuration files.
+pub fn sso_profile_from_env(profile_name: &str) -> Result<SsoProfile> {
+    let var = |name: &'static str| -> Result<String> {
+        std::env::var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .ok_or(Error::MissingEnvVar(name))
+    };
+
+    let sso_profile = SsoProfile {
+        profile_name: profile_name.into(),
+        region: var("AWS_REGION").ok(),
+        output: None,
+        sso_account_id: Some(var("AWS_SSO_ACCOUNT_ID")?),
+        sso_region: var("AWS_SSO_REGION")?,
+        sso_role_name: Some(var("AWS_SSO_ROLE_NAME")?),
+        sso_session: None,
+        sso_start_url: var(START_URL_ENV_VAR)?,
+        properties: BTreeMap::new(),
+    };
+
+    let invalid = |key, reason| Error::InvalidProfileValue {
+        profile: profile_name.into(),
+        key,
+        reason,
+    };
+
+    if !is_account_id(sso_profile.account_id()?) {
+        return Err(invalid(
+            "AWS_SSO_ACCOUNT_ID",
+            format!(
+                "'{}' is not a 12-digit account id",
+                sso_profile.account_id()?
+            ),
+        ));
+    }
+
+    if !is_https_url(sso_profile.sso_start_url.as_str()) {
+        return Err(invalid(
+            START_URL_ENV_VAR,
+            format!("'{}' is not a valid https URL", sso_profile.sso_start_url),
+        ));
+    }
+
+    Ok(sso_profile)
+}
+
+/// The value of a property with surrounding whitespace and any trailing `#` or `;` comment removed, as the SDK keeps
+/// inline comments as part of the value.
+fn profile_value(value: &str) -> &str {
+    let end = value
+        .char_indices()
+        .find(|(i, c)| matches!(c, '#' | ';') && value[..*i].ends_with(char::is_whitespace))
+        .map_or(value.len(), |(i, _)| i);
+
+    value[..end].trim()
+}
+
+/// The longest chain of `source_profile` references followed, including the requested profile itself.
+const MAX_SOURCE_PROFILE_DEPTH: usize = 8;
+
+/// A profile along with the chain of profiles it names in `source_profile`, in order, each key being read from the
+/// first of them which sets it.
+struct Inherited<'a>(Vec<&'a Profile>);
+
+impl Inherited<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.iter().find_map(|profile| profile.get(key))
+    }
+}
+
+/// Follow the `source_profile` references starting at the named profile, failing on references to missing profiles,
+/// cycles, and chains longer than [`MAX_SOURCE_PROFILE_DEPTH`].
+fn source_profile_chain<'a>(
+    profiles: &'a ProfileSet,
+    profile_name: &str,
+    profile: &'a Profile,
+) -> Result<Vec<&'a Profile>> {
+    let invalid = |reason: String| Error::InvalidProfileValue {
+        profile: profile_name.into(),
+        key: "source_profile",
+        reason,
+    };
+
+    let mut names = vec![profile_name.to_string()];
+    let mut chain = vec![profile];
+
+    while let Some(source_name) = chain
+        .last()
+        .and_then(|profile| profile.get("source_profile"))
+    {
+        let source_name = profile_value(source_name);
+
+        if names.iter().any(|name| name == source_name) {
+            return Err(invalid(format!(
+                "profiles form a cycle: {} -> {}",
+                names.join(" -> "),
+                source_name
+            )));
+        }
+
+        if chain.len() == MAX_SOURCE_PROFILE_DEPTH {
+            return Err(invalid(format!(
+                "more than {} profiles are chained together",
+                MAX_SOURCE_PROFILE_DEPTH
+            )));
+        }
+
+        let source = profiles
+            .get_profile(&sdk_profile_name(source_name))
+            .ok_or_else(|| {
+                invalid(format!(
+                    "'{}' refers to a profile which does not exist",
+                    source_name
+                ))
+            })?;
+
+        names.push(source_name.into());
+        chain.push(source);
+    }
+
+    Ok(chain)
+}
+
+/// Whether the value is a well-formed AWS account id: exactly 12 digits.
+pub fn is_account_id(value: &str) -> bool {
+    value.len() == 12 && value.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether the value parses as an absolute `https://` URL with a host.
+fn is_https_url(value: &str) -> bool {
+    match value.parse::<Uri>() {
+        Ok(uri) => {
+            uri.scheme_str() == Some("https") && uri.host().is_some_and(|host| !host.is_empty())
+        }
+        Err(_) => false,
+    }
+}
+
+/// Read the file at the given path, which is treated as empty if it doesn't exist, as the SDK does.
+async fn read_optional_file(path: &Path) -> Result<String> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(Error::io(path)(e)),
+    }
+}
+
+/// Parse the profiles in the given configuration and shared credentials files using the SDK, under the names given by
+/// [`sdk_profile_name`].
+async fn parse_profiles(config: &str, credentials: &str) -> Result<ProfileSet> {
+    let config = encode_profile_names(config, "profile ");
+
+    // the sdk copies the files into strings it never zeroizes, so it's never given the static credentials, which no
+    // sso profile needs
+    let credentials = encode_profile_names(without_static_credentials(credentials).as_str(), "");
+
+    // the sdk reads the files from paths in its environment, so both are given it in memory
+    let fs = Fs::from_slice(&[
+        ("config", config.as_str()),
+        ("credentials", credentials.as_str()),
+    ]);
+
+    let env = Env::from_slice(&[
+        ("AWS_CONFIG_FILE", "config"),
+        ("AWS_SHARED_CREDENTIALS_FILE", "credentials"),
+    ]);
+
+    aws_config::profile::load(&fs, &env)
+        .await
+        .map_err(|e| Error::Parse(format!("profiles: {}", e)))
+}
+
+/// The name under which the SDK parses the profile with the given name: the name itself, with any characters the SDK
+/// doesn't allow in profile names, such as spaces and non-ASCII letters, percent-encoded.
+///
+/// The AWS CLI accepts such names, e.g. `[profile my sso profile]`, but the SDK would otherwise skip these profiles.
+fn sdk_profile_name(profile_name: &str) -> String {
+    let mut encoded = String::with_capacity(profile_name.len());
+
+    for c in profile_name.chars() {
+        match c {
+            c if c.is_ascii_alphanumeric() || "_-/.@:+".contains(c) => encoded.push(c),
+            c => {
+                for byte in c.to_string().bytes() {
+                    encoded.push_str(&format!("%{:02X}", byte));
+                }
+            }
+        }
+    }
+
+    encoded
+}
+
+/// The keys holding static credentials in the shared credentials file.
+const STATIC_CREDENTIAL_KEYS: [&str; 3] = [
+    "aws_access_key_id",
+    "aws_secret_access_key",
+    "aws_session_token",
+];
+
+/// The contents of the shared credentials file without the lines setting [`STATIC_CREDENTIAL_KEYS`], leaving every
+/// other line as it is.
+fn without_static_credentials(contents: &str) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+
+    for line in contents.lines() {
+        let secret = ini_key(line).is_some_and(|key| {
+            STATIC_CREDENTIAL_KEYS
+                .iter()
+                .any(|secret_key| key.eq_ignore_ascii_case(secret_key))
+        });
+
+        if !secret {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+
+    stripped
+}
+
+/// Rewrite the header of every section whose name starts with the prefix so that the rest of its name is the profile
+/// name given by [`sdk_profile_name`], leaving every other line as it is.
+fn encode_profile_names(contents: &str, prefix: &str) -> String {
+    let mut encoded = String::with_capacity(contents.len());
+
+    for line in contents.lines() {
+        let section = ini_section_name(line)
+            .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "));
+
+        match section
+            .as_deref()
+            .and_then(|section| section.strip_prefix(prefix))
+        {
+            Some(profile_name) if sdk_profile_name(profile_name) != profile_name => {
+                encoded.push_str(&format!("[{}{}]", prefix, sdk_profile_name(profile_name)));
+            }
+            _ => encoded.push_str(line),
+        }
+
+        encoded.push('\n');
+    }
+
+    encoded
+}
+
+/// The properties of the `[sso-session NAME]` section with the given name in the AWS configuration file.
+///
+/// The SDK's profile parser ignores these sections, so the configuration file is parsed directly.
+fn sso_session(config: &str, session_name: &str) -> Result<HashMap<String, String>> {
+    ini_sections(config)
+        .remove(format!("sso-session {}", session_name).as_str())
+        .ok_or_else(|| Error::SsoSessionNotFound(session_name.into()))
+}
+
+/// Load every profile in the AWS configuration file, keyed by profile name.
+///
+/// This parses the file directly, as the SDK offers no way of listing profiles. As with the SDK, only `[default]` and
+/// `[profile NAME]` sections are considered profiles.
+pub async fn load_config_profiles() -> Result<BTreeMap<String, HashMap<String, String>>> {
+    let config_file = aws_config_file()?;
+
+    let contents = tokio::fs::read_to_string(&config_file)
+        .await
+        .map_err(Error::io(&config_file))?;
+
+    Ok(ini_sections(contents.as_str())
+        .into_iter()
+        .filter_map(|(section, properties)| match section.as_str() {
+            "default" => Some((section, properties)),
+            _ => section
+                .strip_prefix("profile ")
+                .map(|name| (name.to_string(), properties)),
+        })
+        .collect())
+}
+
+/// Whether the given profile properties look like those of an SSO profile.
+pub fn is_sso_profile(properties: &HashMap<String, String>) -> bool {
+    properties.contains_key("sso_start_url") || properties.contains_key("sso_session")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_static_credentials_before_the_sdk_parses_them() {
+        let stripped = without_static_credentials(
+            "[dev]\n\
+             aws_access_key_id = AKIAEXAMPLE\n\
+             AWS_Secret_Access_Key=secret\n\
+             aws_session_token = token\n\
+             region = us-east-1\n\
+             # aws_secret_access_key = commented\n",
+        );
+
+        assert_eq!(
+            stripped,
+            "[dev]\nregion = us-east-1\n# aws_secret_access_key = commented\n"
+        );
+    }
+}
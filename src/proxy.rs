@@ -0,0 +1,368 @@
+//! Reaching AWS through an HTTP proxy given by the `HTTPS_PROXY`, `HTTP_PROXY`, and `NO_PROXY` environment variables.
+//!
+//! Requests are tunnelled through the proxy using `CONNECT`, so that TLS is still negotiated with AWS itself.
+
+use aws_smithy_client::erase::DynConnector;
+use aws_smithy_client::hyper_ext::Adapter;
+
+use http::Uri;
+
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+
+use hyper_rustls::HttpsConnector;
+
+use rustls::ClientConfig;
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use zeroize::Zeroizing;
+
+use crate::clock::RecordDate;
+use crate::error::{Error, Result};
+
+/// The environment variables naming the proxy for `https` endpoints, in order of precedence.
+const HTTPS_PROXY_ENV_VARS: &[&str] = &["HTTPS_PROXY", "https_proxy"];
+
+/// The environment variables naming the proxy for `http` endpoints, in order of precedence.
+const HTTP_PROXY_ENV_VARS: &[&str] = &["HTTP_PROXY", "http_proxy"];
+
+/// The environment variables listing the hosts to connect to directly, in order of precedence.
+const NO_PROXY_ENV_VARS: &[&str] = &["NO_PROXY", "no_proxy"];
+
+/// The most a proxy's response to `CONNECT` may contain before it's deemed broken.
+const MAX_CONNECT_RESPONSE_LEN: usize = 8192;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The proxies configured in the environment.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Proxies {
+    /// The proxy for `https` endpoints.
+    pub https: Option<Uri>,
+    /// The proxy for `http` endpoints.
+    pub http: Option<Uri>,
+    /// The hosts, and the domains when starting with a dot, to connect to directly, or `*` for all of them, each
+    /// optionally only on the port following a colon.
+    pub no_proxy: Vec<String>,
+}
+
+impl Proxies {
+    /// Read the proxies from the environment, returning `None` if none are set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let proxies = Self {
+            https: proxy_from_env(HTTPS_PROXY_ENV_VARS)?,
+            http: proxy_from_env(HTTP_PROXY_ENV_VARS)?,
+            no_proxy: first_env_var(NO_PROXY_ENV_VARS)
+                .map(|(_, value)| {
+                    value
+                        .split(',')
+                        .map(|host| host.trim().to_ascii_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        Ok(match proxies.https.is_some() || proxies.http.is_some() {
+            true => Some(proxies),
+            false => None,
+        })
+    }
+
+    /// The proxy through which to connect to the given endpoint, if any.
+    pub fn for_uri(&self, uri: &Uri) -> Option<&Uri> {
+        let host = uri.host()?.to_ascii_lowercase();
+        let port = port(uri);
+
+        let bypassed = self.no_proxy.iter().any(|entry| {
+            // a colon in a bare IPv6 address isn't a port, only one after the closing bracket is
+            let (entry, entry_port) = match entry.rsplit_once(':') {
+                Some((name, entry_port)) if !name.contains(':') || name.ends_with(']') => {
+                    match entry_port.parse::<u16>() {
+                        Ok(entry_port) => (name, Some(entry_port)),
+                        Err(_) => (entry.as_str(), None),
+                    }
+                }
+                _ => (entry.as_str(), None),
+            };
+            let domain = entry.trim_start_matches('.');
+
+            entry_port.is_none_or(|entry_port| entry_port == port)
+                && (entry == "*" || host == domain || host.ends_with(&format!(".{}", domain)))
+        });
+
+        match (bypassed, uri.scheme_str()) {
+            (true, _) => None,
+            (false, Some("https")) => self.https.as_ref(),
+            (false, _) => self.http.as_ref(),
+        }
+    }
+}
+
+/// The name and value of the first of the environment variables that is set and not empty.
+fn first_env_var<'a>(names: &[&'a str]) -> Option<(&'a str, String)> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| (*name, value)))
+        .find(|(_, value)| !value.trim().is_empty())
+}
+
+/// Parse the proxy URL in the first of the environment variables set, assuming `http://` if it has no scheme.
+fn proxy_from_env(names: &[&str]) -> Result<Option<Uri>> {
+    let (name, value) = match first_env_var(names) {
+        Some(var) => var,
+        None => return Ok(None),
+    };
+
+    let url = match value.contains("://") {
+        true => value.trim().to_string(),
+        false => format!("http://{}", value.trim()),
+    };
+
+    // the url itself is never echoed, as it may hold the proxy's password
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| Error::Parse(format!("proxy url in {}: {}", name, e)))?;
+
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http"), Some(_)) => Ok(Some(uri)),
+        _ => Err(Error::Parse(format!(
+            "proxy url '{}': only http:// proxies are supported",
+            redacted(&uri)
+        ))),
+    }
+}
+
+/// A connector for the AWS SDK clients honouring the proxies configured in the environment, or `None` if there aren't
+/// any, in which case the SDK's own connector should be used.
+pub fn connector() -> Result<Option<DynConnector>> {
+    let proxies = match Proxies::from_env()? {
+        Some(proxies) => proxies,
+        None => return Ok(None),
+    };
+
+    log::debug!(
+        "Using proxies from the environment: https: {:?}, http: {:?}, no proxy: {:?}",
+        proxies.https.as_ref().map(redacted),
+        proxies.http.as_ref().map(redacted),
+        proxies.no_proxy
+    );
+
+    let mut config = ClientConfig::new();
+
+    config.root_store = match rustls_native_certs::load_native_certs() {
+        Ok(store) => store,
+        Err((Some(store), e)) => {
+            log::warn!("Unable to load all root certificates: {:?}", e);
+            store
+        }
+        Err((None, e)) => {
+            return Err(Error::Proxy {
+                proxy: proxies
+                    .https
+                    .as_ref()
+                    .or(proxies.http.as_ref())
+                    .map(redacted)
+                    .unwrap_or_default(),
+                reason: format!("unable to load root certificates: {}", e),
+            })
+        }
+    };
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    let mut direct = HttpConnector::new();
+    direct.enforce_http(false);
+
+    let tunnel = TunnelConnector {
+        proxies: Arc::new(proxies),
+        direct,
+    };
+
+    Ok(Some(DynConnector::new(RecordDate(
+        Adapter::builder().build(HttpsConnector::from((tunnel, config))),
+    ))))
+}
+
+/// The proxy failure underlying the given error from the AWS SDK, if that's why it failed.
+pub(crate) fn proxy_failure(e: &(dyn std::error::Error + 'static)) -> Option<Error> {
+    let mut source = Some(e);
+
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<TunnelError>() {
+            return Some(Error::Proxy {
+                proxy: e.proxy.clone(),
+                reason: e.source.to_string(),
+            });
+        }
+
+        source = e.source();
+    }
+
+    None
+}
+
+/// A failure to open a tunnel through the proxy.
+#[derive(Debug, thiserror::Error)]
+#[error("unable to connect through proxy {proxy}: {source}")]
+struct TunnelError {
+    proxy: String,
+    source: io::Error,
+}
+
+/// Opens TCP connections to endpoints, tunnelled through the proxy for the endpoint if there is one.
+#[derive(Clone)]
+struct TunnelConnector {
+    proxies: Arc<Proxies>,
+    direct: HttpConnector,
+}
+
+impl Service<Uri> for TunnelConnector {
+    type Response = TcpStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<TcpStream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), BoxError>> {
+        self.direct.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        match self.proxies.for_uri(&dst).cloned() {
+            Some(proxy) => Box::pin(async move {
+                log::debug!("Connecting to {} through proxy {}", dst, redacted(&proxy));
+
+                tunnel(&proxy, &dst).await.map_err(|source| {
+                    TunnelError {
+                        proxy: redacted(&proxy),
+                        source,
+                    }
+                    .into()
+                })
+            }),
+            None => {
+                let connecting = self.direct.call(dst);
+
+                Box::pin(async move { connecting.await.map_err(Into::into) })
+            }
+        }
+    }
+}
+
+/// Connect to the proxy and ask it to open a tunnel to the endpoint's host and port.
+async fn tunnel(proxy: &Uri, dst: &Uri) -> io::Result<TcpStream> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let host = dst
+        .host()
+        .ok_or_else(|| invalid(format!("endpoint {} has no host", dst)))?;
+    let port = port(dst);
+
+    let mut stream = TcpStream::connect((
+        proxy.host().unwrap_or_default(),
+        proxy.port_u16().unwrap_or(80),
+    ))
+    .await?;
+
+    let request = Zeroizing::new(format!(
+        "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n{authorization}\r\n",
+        host = host,
+        port = port,
+        authorization = authorization(proxy).as_deref().map_or("", String::as_str)
+    ));
+
+    stream.write_all(request.as_bytes()).await?;
+
+    // read a byte at a time so as not to consume anything the endpoint sends through the tunnel
+    let mut response = Vec::new();
+
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE_LEN {
+            return Err(invalid("proxy response to CONNECT is too long".into()));
+        }
+
+        match stream.read_u8().await {
+            Ok(byte) => response.push(byte),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(invalid("proxy closed the connection during CONNECT".into()))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(stream),
+        _ => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!(
+                "proxy refused to CONNECT to {}:{}: {}",
+                host, port, status_line
+            ),
+        )),
+    }
+}
+
+/// The port of the endpoint, or the default port for its scheme.
+fn port(uri: &Uri) -> u16 {
+    uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("https") => 443,
+        _ => 80,
+    })
+}
+
+/// The `Proxy-Authorization` header line for the percent-encoded user name and password in the proxy's URL, if any.
+fn authorization(proxy: &Uri) -> Option<Zeroizing<String>> {
+    let (userinfo, _) = proxy.authority()?.as_str().rsplit_once('@')?;
+
+    let userinfo = userinfo.as_bytes();
+    let mut credentials = Zeroizing::new(Vec::with_capacity(userinfo.len() + 1));
+    let mut i = 0;
+
+    while i < userinfo.len() {
+        let escaped = userinfo
+            .get(i + 1..i + 3)
+            .filter(|hex| userinfo[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                credentials.push(byte);
+                i += 3;
+            }
+            None => {
+                credentials.push(userinfo[i]);
+                i += 1;
+            }
+        }
+    }
+
+    // a user name without a password is sent with an empty one
+    if !userinfo.contains(&b':') {
+        credentials.push(b':');
+    }
+
+    Some(Zeroizing::new(format!(
+        "Proxy-Authorization: Basic {}\r\n",
+        Zeroizing::new(aws_smithy_types::base64::encode(credentials.as_slice())).as_str()
+    )))
+}
+
+/// The proxy's URL without the user name and password in it, for logs and errors.
+fn redacted(proxy: &Uri) -> String {
+    let mut parts = proxy.clone().into_parts();
+
+    parts.authority = proxy
+        .authority()
+        .and_then(|authority| authority.as_str().rsplit('@').next()?.parse().ok());
+
+    Uri::from_parts(parts).map_or_else(|_| String::new(), |uri| uri.to_string())
+}
@@ -0,0 +1,176 @@
+//! Defaults for command line flags, read from a TOML settings file.
+//!
+//! Only the subset of TOML needed for flags is understood: top-level keys set to strings, integers, booleans, or arrays
+//! of them, with comments. Keys are named after the flags they set, e.g. `expiry-margin = 120` for `--expiry-margin`.
+
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Defaults for the flags of the same name, overridden by any given on the command line.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    pub format: Option<String>,
+    pub log_format: Option<String>,
+    pub error_format: Option<String>,
+    pub color: Option<String>,
+    pub duration_seconds: Option<u32>,
+    pub duration: Option<String>,
+    pub expiry_margin: Option<u32>,
+    pub warn_before: Option<u32>,
+    pub max_retries: Option<u32>,
+    pub max_concurrency: Option<usize>,
+    pub timeout: Option<u64>,
+    pub cache_dir: Option<PathBuf>,
+    pub token_cache_dir: Vec<PathBuf>,
+    pub token_source: Option<String>,
+    pub passthrough_key: Vec<String>,
+    pub expiration_var_name: Vec<String>,
+    pub env_prefix: Option<String>,
+    pub login: bool,
+    pub no_cache: bool,
+    pub no_region: bool,
+    pub no_comments: bool,
+    pub print_account_alias: bool,
+    pub strict: bool,
+    pub fips: bool,
+    pub dualstack: bool,
+    pub quiet: bool,
+}
+
+impl Settings {
+    /// Read the settings in the given file, or the defaults if it doesn't exist.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let contents = match tokio::fs::read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(Error::io(path)(e)),
+        };
+
+        let invalid =
+            |reason: String| Error::Parse(format!("settings file {}: {}", path.display(), reason));
+
+        let table = parse_toml(contents.as_str()).map_err(invalid)?;
+
+        serde_json::from_value(Value::Object(table)).map_err(|e| invalid(e.to_string()))
+    }
+}
+
+/// Parse a TOML document of top-level keys into a JSON object, to be deserialized with serde.
+fn parse_toml(contents: &str) -> std::result::Result<Map<String, Value>, String> {
+    let mut table = Map::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_error = |reason: &str| format!("line {}: {}", index + 1, reason);
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            return Err(line_error("tables are not supported"));
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| line_error("expected `key = value`"))?;
+
+        let key = key.trim().trim_matches('"');
+
+        if key.is_empty() {
+            return Err(line_error("expected a key before `=`"));
+        }
+
+        let (value, rest) = parse_value(value.trim_start()).map_err(|e| line_error(&e))?;
+
+        if !(rest.is_empty() || rest.starts_with('#')) {
+            return Err(line_error(&format!(
+                "unexpected `{}` after the value",
+                rest
+            )));
+        }
+
+        if table.insert(key.into(), value).is_some() {
+            return Err(line_error(&format!("`{}` is set more than once", key)));
+        }
+    }
+
+    Ok(table)
+}
+
+/// Parse the TOML value at the start of the input, returning it with the rest of the input, less leading whitespace.
+fn parse_value(input: &str) -> std::result::Result<(Value, &str), String> {
+    let (value, rest) = match input.chars().next() {
+        Some('"') => parse_basic_string(&input[1..])?,
+        Some('\'') => {
+            let end = input[1..].find('\'').ok_or("unterminated string")?;
+
+            (Value::String(input[1..=end].into()), &input[end + 2..])
+        }
+        Some('[') => {
+            let mut values = Vec::new();
+            let mut rest = input[1..].trim_start();
+
+            while !rest.starts_with(']') {
+                let (value, after) = parse_value(rest)?;
+
+                values.push(value);
+
+                rest = match after.strip_prefix(',') {
+                    Some(after) => after.trim_start(),
+                    None if after.starts_with(']') => after,
+                    None => return Err("expected `,` or `]` in array".into()),
+                };
+            }
+
+            (Value::Array(values), &rest[1..])
+        }
+        _ => {
+            let end = input
+                .find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#')
+                .unwrap_or(input.len());
+
+            let value = match &input[..end] {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                literal => literal
+                    .replace('_', "")
+                    .parse::<i64>()
+                    .map(|integer| Value::Number(Number::from(integer)))
+                    .map_err(|_| format!("unsupported value `{}`", literal))?,
+            };
+
+            (value, &input[end..])
+        }
+    };
+
+    Ok((value, rest.trim_start()))
+}
+
+/// Parse the rest of a double-quoted string, after its opening quote, unescaping it.
+fn parse_basic_string(input: &str) -> std::result::Result<(Value, &str), String> {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Value::String(value), &input[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(c) => return Err(format!("unsupported escape `\\{}`", c)),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err("unterminated string".into())
+}
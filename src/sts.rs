@@ -0,0 +1,140 @@
+//! Role chaining: assuming another role with the role credentials issued by SSO.
+
+use aws_sdk_sts::{
+    Client as StsClient, Config as StsConfig, Credentials as StsCredentials, Endpoint,
+    Region as StsRegion, RetryConfig,
+};
+
+use time::OffsetDateTime;
+
+use crate::credentials::{parse_endpoint_url, EndpointVariant};
+use crate::error::{Error, Result};
+use crate::proxy::proxy_failure;
+use crate::SsoCredentials;
+
+/// Options controlling how [`assume_role`] assumes a role.
+#[derive(Clone, Debug)]
+pub struct AssumeRoleOptions {
+    /// The ARN of the role to assume.
+    pub role_arn: String,
+    /// The external id the role's trust policy requires, if any.
+    pub external_id: Option<String>,
+    /// The serial number or ARN of the MFA device, for roles whose trust policy requires MFA.
+    pub mfa_serial: Option<String>,
+    /// The current code shown by the MFA device given by `mfa_serial`.
+    pub token_code: Option<String>,
+    /// The name of the role session, which appears in CloudTrail and the assumed role's ARN.
+    pub role_session_name: String,
+    /// The region whose STS endpoint to call.
+    pub region: String,
+    /// The URL of the STS API to use instead of the one for the region.
+    pub endpoint_url: Option<String>,
+    /// Which variant of the STS endpoint for the region to use, unless `endpoint_url` is given.
+    pub variant: EndpointVariant,
+}
+
+/// A role identified by an ARN such as `arn:aws:iam::123456789012:role/path/Name`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleArn {
+    pub account_id: String,
+    /// The role's name, without its path.
+    pub role_name: String,
+}
+
+/// Parse an IAM role ARN into the account and the name of the role.
+pub fn parse_role_arn(arn: &str) -> Result<RoleArn> {
+    let invalid = || Error::Parse(format!("role arn '{}'", arn));
+
+    match arn.splitn(6, ':').collect::<Vec<_>>().as_slice() {
+        ["arn", partition, "iam", "", account_id, resource] if !partition.is_empty() => {
+            let role_name = resource
+                .strip_prefix("role/")
+                .and_then(|path| path.rsplit('/').next())
+                .filter(|role_name| !role_name.is_empty())
+                .ok_or_else(invalid)?;
+
+            match crate::profile::is_account_id(account_id) {
+                true => Ok(RoleArn {
+                    account_id: account_id.to_string(),
+                    role_name: role_name.into(),
+                }),
+                false => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Assume the role given in the options using the SSO-issued credentials, returning the temporary credentials of the
+/// assumed role.
+pub async fn assume_role(
+    credentials: &SsoCredentials,
+    options: &AssumeRoleOptions,
+) -> Result<SsoCredentials> {
+    // STS would only reject the request as unauthorized, without saying why
+    if options.mfa_serial.is_some() && options.token_code.is_none() {
+        return Err(Error::MissingTokenCode(options.role_arn.clone()));
+    }
+
+    let caller = StsCredentials::new(
+        credentials.access_key_id.as_str(),
+        credentials.secret_access_key.as_str(),
+        Some(credentials.session_token.clone()),
+        Some(credentials.expires_at.into()),
+        "aws-sso-env",
+    );
+
+    let mut config = StsConfig::builder()
+        .region(StsRegion::new(options.region.clone()))
+        .credentials_provider(caller)
+        .retry_config(RetryConfig::disabled());
+
+    let endpoint_url = match options.endpoint_url.clone() {
+        Some(endpoint_url) => Some(endpoint_url),
+        None => options
+            .variant
+            .endpoint_url("sts", options.region.as_str())?,
+    };
+
+    if let Some(endpoint_url) = endpoint_url.as_deref() {
+        config = config.endpoint_resolver(Endpoint::immutable(parse_endpoint_url(endpoint_url)?));
+    }
+
+    let client = StsClient::from_conf_conn(config.build(), crate::clock::connector()?);
+
+    let assumed = client
+        .assume_role()
+        .role_arn(options.role_arn.as_str())
+        .role_session_name(options.role_session_name.as_str())
+        .set_external_id(options.external_id.clone())
+        .set_serial_number(options.mfa_serial.clone())
+        .set_token_code(options.token_code.clone())
+        .send()
+        .await
+        .map_err(|e| {
+            proxy_failure(&e).unwrap_or_else(|| Error::StsApi {
+                operation: "AssumeRole",
+                source: e.into(),
+            })
+        })?
+        .credentials
+        .ok_or(Error::IncompleteResponse("any assumed role credentials"))?;
+
+    let expiration = assumed
+        .expiration
+        .ok_or(Error::IncompleteResponse("an assumed role expiration"))?;
+
+    Ok(SsoCredentials {
+        access_key_id: assumed
+            .access_key_id
+            .ok_or(Error::IncompleteResponse("an assumed role access key id"))?,
+        secret_access_key: assumed.secret_access_key.ok_or(Error::IncompleteResponse(
+            "an assumed role secret access key",
+        ))?,
+        session_token: assumed
+            .session_token
+            .ok_or(Error::IncompleteResponse("an assumed role session token"))?,
+        expires_at: OffsetDateTime::from_unix_timestamp_nanos(expiration.as_nanos())
+            .map_err(|e| Error::Parse(format!("assumed role expiration: {}", e)))?,
+    })
+}
@@ -0,0 +1,636 @@
+//! Loading of the SSO tokens cached by `aws sso login`, or stored in the OS keyring.
+
+use aws_sdk_ssooidc::{Client as OidcClient, Config as OidcConfig, Endpoint, Region as OidcRegion};
+
+use futures::future::BoxFuture;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use sha1::Sha1;
+
+use std::path::{Path, PathBuf};
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::credentials::{parse_endpoint_url, REDACTED};
+use crate::error::{Error, Result};
+use crate::files::replace_private_file;
+use crate::keyring::{Keyring, TOKEN_SERVICE};
+use crate::proxy::proxy_failure;
+use crate::SsoProfile;
+
+/// An SSO access token, as cached by the AWS CLI in `~/.aws/sso/cache` after `aws sso login`, wiped from memory when
+/// dropped, and masked when debug formatted.
+#[derive(Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedSsoToken {
+    pub access_token: String,
+    /// When the token expires, cached either as an RFC3339 timestamp or as a Unix epoch number of seconds or
+    /// milliseconds.
+    #[serde(deserialize_with = "expiry::deserialize")]
+    #[zeroize(skip)]
+    pub expires_at: OffsetDateTime,
+    pub region: String,
+    pub start_url: String,
+    /// The OIDC refresh token, cached by the AWS CLI for `sso-session` profiles, with which a new access token can be
+    /// created without logging in again.
+    pub refresh_token: Option<String>,
+    /// The id of the OIDC client registered by the AWS CLI, needed alongside the refresh token.
+    pub client_id: Option<String>,
+    /// The secret of the OIDC client registered by the AWS CLI, needed alongside the refresh token.
+    pub client_secret: Option<String>,
+    /// The cache file the token was read from, to which a refreshed token is written back.
+    #[serde(skip)]
+    #[zeroize(skip)]
+    pub cache_file: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for CachedSsoToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedSsoToken")
+            .field("access_token", &REDACTED)
+            .field("expires_at", &self.expires_at)
+            .field("region", &self.region)
+            .field("start_url", &self.start_url)
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| REDACTED),
+            )
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| REDACTED),
+            )
+            .field("cache_file", &self.cache_file)
+            .finish()
+    }
+}
+
+/// Deserialization of a token's expiry from either an RFC3339 string or a Unix epoch number, for use with
+/// `#[serde(deserialize_with = "expiry::deserialize")]`.
+mod expiry {
+    use serde::{de, Deserialize, Deserializer};
+
+    use time::format_description::well_known::Rfc3339;
+    use time::OffsetDateTime;
+
+    /// Epoch numbers at least this large are taken to be milliseconds: as seconds, they would be over 3000 years away.
+    const MILLISECONDS_THRESHOLD: i64 = 100_000_000_000;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Encoded {
+        Epoch(i64),
+        Rfc3339(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OffsetDateTime, D::Error> {
+        match Encoded::deserialize(deserializer)? {
+            Encoded::Epoch(epoch) => {
+                let nanoseconds = if epoch.abs() >= MILLISECONDS_THRESHOLD {
+                    i128::from(epoch) * 1_000_000
+                } else {
+                    i128::from(epoch) * 1_000_000_000
+                };
+
+                OffsetDateTime::from_unix_timestamp_nanos(nanoseconds)
+                    .map_err(|e| de::Error::custom(format!("token expiry {}: {}", epoch, e)))
+            }
+            Encoded::Rfc3339(encoded) => OffsetDateTime::parse(encoded.as_str(), &Rfc3339)
+                .map_err(|e| de::Error::custom(format!("token expiry '{}': {}", encoded, e))),
+        }
+    }
+}
+
+/// Find and deserialize the cached SSO token for a profile within the given cache directory.
+///
+/// If no token is cached under the expected filenames, every token in the directory is searched for one issued for the
+/// profile's start url. Of several matching tokens, the one expiring last is returned.
+///
+/// Tokens cached under the expected filenames for another start url are ignored with a warning. Returns `None` if no
+/// token is cached for the profile or the cached token can't be read.
+pub async fn load_cached_token(
+    sso_profile: &SsoProfile,
+    cache_dir: &Path,
+) -> Option<CachedSsoToken> {
+    if !cache_dir.is_dir() {
+        log::debug!(
+            "SSO credentials cache directory does not exist: {}",
+            cache_dir.display()
+        );
+        return None;
+    }
+
+    let mut tokens = Vec::new();
+
+    for (_, cache_file) in cache_files(sso_profile, cache_dir) {
+        if let Some(token) = read_cached_token(cache_file.as_path()).await {
+            match issued_for(&token, sso_profile) {
+                Ok(()) => tokens.push(token),
+                Err(reason) => log::warn!(
+                    "Ignoring the cached SSO token in {}: {}",
+                    cache_file.display(),
+                    reason
+                ),
+            }
+        }
+    }
+
+    if tokens.is_empty() {
+        log::debug!(
+            "Cache file for profile '{}' does not exist, searching the cache for its start url.",
+            sso_profile.profile_name
+        );
+
+        tokens = scan_cached_tokens(cache_dir, sso_profile).await;
+    }
+
+    latest_token(tokens)
+}
+
+/// Find the cached SSO token for a profile within each of the given cache directories in turn, returning the first
+/// which expires after the cutoff, e.g. to use the tokens cached by other SSO tools alongside those of the AWS CLI.
+///
+/// When every token found has expired, the one expiring last is returned, so that it can still be refreshed. Returns
+/// `None` if no token is cached for the profile in any of the directories.
+pub async fn load_cached_token_from(
+    sso_profile: &SsoProfile,
+    cache_dirs: &[PathBuf],
+    expiry_cutoff: OffsetDateTime,
+) -> Option<CachedSsoToken> {
+    let mut expired = Vec::new();
+
+    for cache_dir in cache_dirs {
+        match load_cached_token(sso_profile, cache_dir.as_path()).await {
+            Some(token) if token.expires_at > expiry_cutoff => return Some(token),
+            Some(token) => {
+                log::debug!(
+                    "Cached SSO token in {} has expired, searching the next cache directory.",
+                    cache_dir.display()
+                );
+                expired.push(token);
+            }
+            None => (),
+        }
+    }
+
+    latest_token(expired)
+}
+
+/// Where cached SSO tokens are read from, so that tokens kept somewhere other than the AWS CLI's cache directory can be
+/// used in the same way.
+pub trait TokenSource {
+    /// Find the token cached for the profile, returning `None` if there's none or it can't be read, and failing only if
+    /// the source itself can't be read, e.g. because the keyring's tool isn't installed.
+    fn load<'a>(
+        &'a self,
+        sso_profile: &'a SsoProfile,
+    ) -> BoxFuture<'a, Result<Option<CachedSsoToken>>>;
+
+    /// The reason the token cached for the profile can't be read, for when [`TokenSource::load`] finds none, so that
+    /// a malformed token isn't mistaken for a missing one. Returns `None` if no token is cached for the profile.
+    fn unreadable<'a>(&'a self, sso_profile: &'a SsoProfile) -> BoxFuture<'a, Option<Error>>;
+}
+
+/// The tokens cached in files by `aws sso login`, searched for in each of the directories in turn as by
+/// [`load_cached_token_from`].
+#[derive(Clone, Debug)]
+pub struct FileTokenSource {
+    pub cache_dirs: Vec<PathBuf>,
+    /// Tokens expiring before this time are passed over for those in later directories.
+    pub expiry_cutoff: OffsetDateTime,
+}
+
+impl TokenSource for FileTokenSource {
+    fn load<'a>(
+        &'a self,
+        sso_profile: &'a SsoProfile,
+    ) -> BoxFuture<'a, Result<Option<CachedSsoToken>>> {
+        Box::pin(async move {
+            Ok(load_cached_token_from(sso_profile, &self.cache_dirs, self.expiry_cutoff).await)
+        })
+    }
+
+    fn unreadable<'a>(&'a self, sso_profile: &'a SsoProfile) -> BoxFuture<'a, Option<Error>> {
+        Box::pin(async move {
+            for cache_dir in self.cache_dirs.iter() {
+                if let Some(e) = unreadable_cached_token(sso_profile, cache_dir.as_path()).await {
+                    return Some(e);
+                }
+            }
+
+            None
+        })
+    }
+}
+
+/// Tokens stored in the OS keyring rather than in plaintext files, each as the JSON the AWS CLI would cache, under the
+/// [`TOKEN_SERVICE`] service and one of the keys the file would be named after: the profile's sso-session name, or its
+/// start url.
+#[derive(Clone, Debug, Default)]
+pub struct KeyringTokenSource {
+    pub keyring: Keyring,
+}
+
+impl KeyringTokenSource {
+    /// Read and deserialize the token stored under the key, returning `None` if there's none, or the reason the token
+    /// stored can't be deserialized. Fails if the keyring itself can't be read, as a missing tool isn't a missing token.
+    async fn lookup(&self, key: &str) -> Result<Option<Result<CachedSsoToken>>> {
+        let secret = match self.keyring.lookup(TOKEN_SERVICE, key).await? {
+            Some(secret) => secret,
+            None => {
+                log::debug!("No SSO token stored in the keyring under '{}'", key);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(
+            serde_json::from_slice::<CachedSsoToken>(secret.as_slice()).map_err(|e| {
+                Error::KeyringTokenUnreadable {
+                    key: key.into(),
+                    reason: e.to_string(),
+                }
+            }),
+        ))
+    }
+}
+
+impl TokenSource for KeyringTokenSource {
+    fn load<'a>(
+        &'a self,
+        sso_profile: &'a SsoProfile,
+    ) -> BoxFuture<'a, Result<Option<CachedSsoToken>>> {
+        Box::pin(async move {
+            let mut tokens = Vec::new();
+
+            for cache_key in cache_keys(sso_profile) {
+                match self.lookup(cache_key.as_str()).await? {
+                    Some(Ok(token)) => match issued_for(&token, sso_profile) {
+                        Ok(()) => tokens.push(token),
+                        Err(reason) => log::warn!(
+                            "Ignoring the SSO token in the keyring under '{}': {}",
+                            cache_key,
+                            reason
+                        ),
+                    },
+                    Some(Err(e)) => log::debug!("{}", e),
+                    None => (),
+                }
+            }
+
+            Ok(latest_token(tokens))
+        })
+    }
+
+    fn unreadable<'a>(&'a self, sso_profile: &'a SsoProfile) -> BoxFuture<'a, Option<Error>> {
+        Box::pin(async move {
+            for cache_key in cache_keys(sso_profile) {
+                let unreadable = |reason: String| Error::KeyringTokenUnreadable {
+                    key: cache_key.clone(),
+                    reason,
+                };
+
+                match self.lookup(cache_key.as_str()).await {
+                    Ok(Some(Ok(token))) => {
+                        if let Err(reason) = issued_for(&token, sso_profile) {
+                            return Some(unreadable(reason));
+                        }
+                    }
+                    Ok(Some(Err(e))) | Err(e) => return Some(e),
+                    Ok(None) => (),
+                }
+            }
+
+            None
+        })
+    }
+}
+
+/// The files in which the token for a profile may be cached, in order of preference, with the key hashed to name each.
+pub fn cache_files(sso_profile: &SsoProfile, cache_dir: &Path) -> Vec<(String, PathBuf)> {
+    cache_keys(sso_profile)
+        .into_iter()
+        .map(|cache_key| {
+            let cache_file = cache_dir.join(format!("{}.json", Sha1::from(&cache_key).hexdigest()));
+            (cache_key, cache_file)
+        })
+        .collect()
+}
+
+/// The keys under which the token for a profile may be cached, each hashed to form a cache filename.
+///
+/// The AWS CLI keys the cache by the sso-session name when there is one, and by the start url otherwise. As the start
+/// url is sometimes stored with a trailing slash and sometimes without, both variants are tried.
+pub fn cache_keys(sso_profile: &SsoProfile) -> Vec<String> {
+    let start_url = sso_profile.sso_start_url.as_str();
+
+    let mut cache_keys: Vec<String> = sso_profile.sso_session.iter().cloned().collect();
+
+    cache_keys.push(start_url.into());
+    cache_keys.push(match start_url.strip_suffix('/') {
+        Some(start_url) => start_url.into(),
+        None => format!("{}/", start_url),
+    });
+
+    cache_keys
+}
+
+/// Check that the token was issued for the profile's start url, failing with the reason it wasn't. As the cache is
+/// keyed by a hash of the start url or sso-session name, a stale file, a reused session name, or a collision could
+/// otherwise hand over a token for another SSO instance.
+fn issued_for(token: &CachedSsoToken, sso_profile: &SsoProfile) -> std::result::Result<(), String> {
+    match normalize_start_url(token.start_url.as_str())
+        == normalize_start_url(sso_profile.sso_start_url.as_str())
+    {
+        true => Ok(()),
+        false => Err(format!(
+            "issued for start url '{}' rather than the profile's '{}'",
+            token.start_url, sso_profile.sso_start_url
+        )),
+    }
+}
+
+/// The start url as compared between tokens and profiles: without surrounding whitespace or a trailing slash, and in
+/// lowercase, as the AWS CLI stores it however it was written in the config file.
+fn normalize_start_url(start_url: &str) -> String {
+    start_url.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+/// Deserialize the cached token in the given file, if it exists and can be read.
+async fn read_cached_token(cache_file: &Path) -> Option<CachedSsoToken> {
+    if !cache_file.is_file() {
+        return None;
+    }
+
+    parse_cached_token(cache_file)
+        .await
+        .map_err(|e| log::debug!("{}", e))
+        .ok()
+}
+
+/// Deserialize the cached token in the given file, failing with the reason it can't be read.
+async fn parse_cached_token(cache_file: &Path) -> Result<CachedSsoToken> {
+    let unreadable = |reason: String| Error::CacheTokenUnreadable {
+        path: cache_file.to_path_buf(),
+        reason,
+    };
+
+    let mut contents = tokio::fs::read_to_string(cache_file)
+        .await
+        .map_err(|e| unreadable(e.to_string()))?;
+
+    let token = serde_json::from_str::<CachedSsoToken>(contents.as_str());
+
+    contents.zeroize();
+
+    let mut token = token.map_err(|e| unreadable(e.to_string()))?;
+
+    token.cache_file = Some(cache_file.to_path_buf());
+
+    Ok(token)
+}
+
+/// The reason the token cached for a profile under one of its expected filenames can't be read, for when
+/// [`load_cached_token`] finds none, so that a malformed cache file isn't mistaken for a missing one.
+///
+/// Returns `None` if none of the profile's cache files exist.
+pub async fn unreadable_cached_token(sso_profile: &SsoProfile, cache_dir: &Path) -> Option<Error> {
+    for (_, cache_file) in cache_files(sso_profile, cache_dir) {
+        if cache_file.is_file() {
+            match parse_cached_token(cache_file.as_path()).await {
+                Ok(token) => {
+                    if let Err(reason) = issued_for(&token, sso_profile) {
+                        return Some(Error::CacheTokenUnreadable {
+                            path: cache_file,
+                            reason,
+                        });
+                    }
+                }
+                Err(e) => return Some(e),
+            }
+        }
+    }
+
+    None
+}
+
+/// Read every token in the cache directory issued for the profile's start url, regardless of its filename, as other
+/// tools and versions of the AWS CLI may key the cache differently.
+async fn scan_cached_tokens(cache_dir: &Path, sso_profile: &SsoProfile) -> Vec<CachedSsoToken> {
+    let mut tokens = Vec::new();
+
+    let mut entries = match tokio::fs::read_dir(cache_dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::debug!("Unable to list SSO cache directory: {:?}", e);
+            return tokens;
+        }
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+
+        // the cache also holds client registrations and our own credentials, which aren't tokens, so aren't errors
+        let token = match tokio::fs::read_to_string(&path).await {
+            Ok(mut contents) => {
+                let token = serde_json::from_str::<CachedSsoToken>(contents.as_str()).ok();
+                contents.zeroize();
+                token
+            }
+            Err(_) => None,
+        };
+
+        if let Some(mut token) = token {
+            if issued_for(&token, sso_profile).is_ok() {
+                log::debug!("Found a cached SSO token in {}", path.display());
+                token.cache_file = Some(path);
+                tokens.push(token);
+            }
+        }
+    }
+
+    tokens
+}
+
+/// The token which expires furthest in the future.
+fn latest_token(tokens: Vec<CachedSsoToken>) -> Option<CachedSsoToken> {
+    tokens.into_iter().max_by_key(|token| token.expires_at)
+}
+
+/// Create a new access token from the cached token's refresh token using the SSO OIDC `CreateToken` API, and write it
+/// back to the cache file the token was read from, so that the AWS CLI and SDKs see it too.
+///
+/// The endpoint URL, if given, is used instead of the OIDC endpoint for the token's region. Returns `None` if the token
+/// was cached without a refresh token and OIDC client, as only `sso-session` profiles have them.
+pub async fn refresh_cached_token(
+    token: &CachedSsoToken,
+    endpoint_url: Option<&str>,
+) -> Result<Option<CachedSsoToken>> {
+    let (refresh_token, client_id, client_secret) =
+        match (&token.refresh_token, &token.client_id, &token.client_secret) {
+            (Some(refresh_token), Some(client_id), Some(client_secret)) => {
+                (refresh_token, client_id, client_secret)
+            }
+            _ => return Ok(None),
+        };
+
+    let mut config = OidcConfig::builder().region(OidcRegion::new(token.region.clone()));
+
+    if let Some(endpoint_url) = endpoint_url {
+        config = config.endpoint_resolver(Endpoint::immutable(parse_endpoint_url(endpoint_url)?));
+    }
+
+    let client = OidcClient::from_conf_conn(config.build(), crate::clock::connector()?);
+
+    let response = client
+        .create_token()
+        .grant_type("refresh_token")
+        .client_id(client_id.clone())
+        .client_secret(client_secret.clone())
+        .refresh_token(refresh_token.clone())
+        .send()
+        .await
+        .map_err(|e| {
+            proxy_failure(&e).unwrap_or_else(|| Error::SsoApi {
+                operation: "CreateToken",
+                source: e.into(),
+            })
+        })?;
+
+    // whole seconds, as written by the aws cli
+    let expires_at = OffsetDateTime::from_unix_timestamp(
+        OffsetDateTime::now_utc().unix_timestamp() + i64::from(response.expires_in),
+    )
+    .map_err(|e| Error::Parse(format!("refreshed token expiry: {}", e)))?;
+
+    let refreshed = CachedSsoToken {
+        access_token: response
+            .access_token
+            .ok_or(Error::IncompleteResponse("an access token"))?,
+        expires_at,
+        region: token.region.clone(),
+        start_url: token.start_url.clone(),
+        // the refresh token may or may not be rotated
+        refresh_token: response
+            .refresh_token
+            .or_else(|| token.refresh_token.clone()),
+        client_id: token.client_id.clone(),
+        client_secret: token.client_secret.clone(),
+        cache_file: token.cache_file.clone(),
+    };
+
+    // the refreshed token is still usable for this run if it can't be cached
+    if let Some(cache_file) = refreshed.cache_file.as_deref() {
+        if let Err(e) = write_refreshed_token(cache_file, &refreshed).await {
+            log::warn!("Unable to cache the refreshed SSO token: {:?}", e);
+        }
+    }
+
+    Ok(Some(refreshed))
+}
+
+/// Update the token in the cache file in place, keeping any other properties the AWS CLI stored alongside it.
+async fn write_refreshed_token(cache_file: &Path, token: &CachedSsoToken) -> Result<()> {
+    let mut contents = tokio::fs::read_to_string(cache_file)
+        .await
+        .map_err(Error::io(cache_file))?;
+
+    let parsed = serde_json::from_str::<Value>(contents.as_str());
+
+    contents.zeroize();
+
+    let mut cached = match parsed {
+        Ok(Value::Object(cached)) => cached,
+        _ => {
+            return Err(Error::Parse(format!(
+                "cached SSO token in {}",
+                cache_file.display()
+            )))
+        }
+    };
+
+    let expires_at = token
+        .expires_at
+        .format(&Rfc3339)
+        .map_err(|e| Error::Format(format!("token expiry: {}", e)))?;
+
+    cached.insert("accessToken".into(), token.access_token.clone().into());
+    cached.insert("expiresAt".into(), expires_at.into());
+
+    if let Some(refresh_token) = token.refresh_token.as_deref() {
+        cached.insert("refreshToken".into(), refresh_token.into());
+    }
+
+    let mut cached = Value::Object(cached);
+    let mut encoded = cached.to_string();
+
+    zeroize_json(&mut cached);
+
+    let result = replace_private_file(cache_file, encoded.as_bytes()).await;
+
+    encoded.zeroize();
+
+    result
+}
+
+/// Wipe every string within the JSON value, as the cached token holds several secrets.
+fn zeroize_json(value: &mut Value) {
+    match value {
+        Value::String(string) => string.zeroize(),
+        Value::Array(values) => values.iter_mut().for_each(zeroize_json),
+        Value::Object(object) => object.values_mut().for_each(zeroize_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+
+    #[test]
+    fn zeroizes_every_secret_on_drop() {
+        assert_zeroize_on_drop::<CachedSsoToken>();
+
+        let mut token = CachedSsoToken {
+            access_token: "access".into(),
+            expires_at: OffsetDateTime::UNIX_EPOCH,
+            region: "us-east-1".into(),
+            start_url: "https://example.awsapps.com/start".into(),
+            refresh_token: Some("refresh".into()),
+            client_id: Some("client".into()),
+            client_secret: Some("client-secret".into()),
+            cache_file: None,
+        };
+
+        token.zeroize();
+
+        // destructured without `..`, so that adding a field fails to compile until it's covered here
+        let CachedSsoToken {
+            access_token,
+            expires_at: _,
+            region: _,
+            start_url: _,
+            refresh_token,
+            client_id,
+            client_secret,
+            cache_file: _,
+        } = &token;
+
+        assert!(access_token.is_empty());
+        assert!(refresh_token.is_none());
+        assert!(client_id.is_none());
+        assert!(client_secret.is_none());
+    }
+}
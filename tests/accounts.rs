@@ -0,0 +1,170 @@
+mod common;
+
+use common::{error_body, stderr, stdout, FakeSso, Fixture};
+
+#[tokio::test]
+async fn lists_roles_across_pages() {
+    let sso = FakeSso::start(|index| {
+        match index {
+        0 => (
+            200,
+            r#"{"nextToken":"page-2","roleList":[{"roleName":"Developer","accountId":"123456789012"}]}"#.into(),
+        ),
+        _ => (
+            200,
+            r#"{"roleList":[{"roleName":"ReadOnly","accountId":"123456789012"}]}"#.into(),
+        ),
+    }
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["roles", "dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "Developer\nReadOnly\n");
+
+    let requests = sso.requests();
+
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[0].path.starts_with("/assignment/roles?"),
+        "{}",
+        requests[0].path
+    );
+    assert!(
+        requests[0].path.contains("account_id=123456789012"),
+        "{}",
+        requests[0].path
+    );
+    assert!(
+        !requests[0].path.contains("next_token"),
+        "{}",
+        requests[0].path
+    );
+    assert!(
+        requests[1].path.contains("next_token=page-2"),
+        "{}",
+        requests[1].path
+    );
+    assert_eq!(
+        requests[1].bearer_token.as_deref(),
+        Some("test-access-token")
+    );
+}
+
+#[tokio::test]
+async fn lists_roles_as_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            r#"{"roleList":[{"roleName":"ReadOnly","accountId":"210987654321"}]}"#.into(),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["roles", "--json", "prod"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!([{"accountId": "210987654321", "roleName": "ReadOnly"}])
+    );
+}
+
+#[tokio::test]
+async fn listing_roles_with_a_revoked_token_exits_with_code_5() {
+    let sso = FakeSso::start(|_| {
+        (
+            401,
+            error_body(
+                "UnauthorizedException",
+                "Session token not found or invalid",
+            ),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["roles", "dev"]).await;
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn lists_accounts_across_pages() {
+    let sso = FakeSso::start(|index| {
+        match index {
+        0 => (
+            200,
+            r#"{"nextToken":"page-2","accountList":[{"accountId":"123456789012","accountName":"dev","emailAddress":"dev@example.com"}]}"#.into(),
+        ),
+        _ => (
+            200,
+            r#"{"accountList":[{"accountId":"210987654321","accountName":"prod","emailAddress":"prod@example.com"}]}"#.into(),
+        ),
+    }
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["accounts", "dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "123456789012\tdev\n210987654321\tprod\n");
+
+    let requests = sso.requests();
+
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[0].path.starts_with("/assignment/accounts"),
+        "{}",
+        requests[0].path
+    );
+    assert!(
+        requests[1].path.contains("next_token=page-2"),
+        "{}",
+        requests[1].path
+    );
+}
+
+#[tokio::test]
+async fn lists_accounts_as_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            r#"{"accountList":[{"accountId":"210987654321","accountName":"prod","emailAddress":"prod@example.com"}]}"#.into(),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["accounts", "--json", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!([{
+            "accountId": "210987654321",
+            "accountName": "prod",
+            "emailAddress": "prod@example.com"
+        }])
+    );
+}
+
+#[tokio::test]
+async fn listing_accounts_without_a_token_exits_with_code_4() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    std::fs::remove_dir_all(fixture.cache_dir()).unwrap();
+
+    let output = fixture.run(&sso.url(), &["accounts", "dev"]).await;
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(sso.requests().is_empty());
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{role_credentials, stderr, stdout, FakeSso, Fixture};
+
+/// A successful `ListAccountAliases` response body naming the given alias.
+fn account_aliases(alias: &str) -> String {
+    format!(
+        "<ListAccountAliasesResponse xmlns=\"https://iam.amazonaws.com/doc/2010-05-08/\">\
+         <ListAccountAliasesResult><IsTruncated>false</IsTruncated>\
+         <AccountAliases><member>{}</member></AccountAliases></ListAccountAliasesResult>\
+         <ResponseMetadata><RequestId>example</RequestId></ResponseMetadata>\
+         </ListAccountAliasesResponse>",
+        alias
+    )
+}
+
+#[tokio::test]
+async fn exports_and_caches_the_account_alias() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let iam = FakeSso::start(|_| (200, account_aliases("example-dev")));
+    let fixture = Fixture::new();
+    let iam_url = iam.url();
+
+    let args = [
+        "--iam-endpoint-url",
+        iam_url.as_str(),
+        "--print-account-alias",
+        "--refresh",
+        "dev",
+    ];
+
+    let first = fixture.run(&sso.url(), &args).await;
+
+    assert!(first.status.success(), "{}", stderr(&first));
+
+    let stdout = stdout(&first);
+
+    assert!(
+        stdout.contains("# account alias: example-dev\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("export AWS_ACCOUNT_ALIAS=example-dev\n"),
+        "{}",
+        stdout
+    );
+
+    let second = fixture.run(&sso.url(), &args).await;
+
+    assert!(second.status.success(), "{}", stderr(&second));
+    assert!(common::stdout(&second).contains("export AWS_ACCOUNT_ALIAS=example-dev\n"));
+
+    // the credentials are fetched twice, but the alias only once
+    assert_eq!(sso.requests().len(), 2);
+    assert_eq!(iam.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn skips_the_account_alias_when_denied() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let iam = FakeSso::start(|_| {
+        (
+            403,
+            "<ErrorResponse><Error><Type>Sender</Type><Code>AccessDenied</Code>\
+             <Message>not authorized to perform: iam:ListAccountAliases</Message></Error>\
+             </ErrorResponse>"
+                .into(),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["--iam-endpoint-url", iam.url().as_str()])
+        .args(["--print-account-alias", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    assert!(!stdout(&output).contains("AWS_ACCOUNT_ALIAS"));
+    assert!(!stderr(&output).contains("WARN"), "{}", stderr(&output));
+    assert_eq!(iam.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn includes_the_account_alias_in_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let iam = FakeSso::start(|_| (200, account_aliases("example-dev")));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--iam-endpoint-url",
+                iam.url().as_str(),
+                "--print-account-alias",
+                "--json",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(json["accountAlias"], "example-dev");
+}
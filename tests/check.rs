@@ -0,0 +1,184 @@
+mod common;
+
+use common::{stderr, stdout, FakeSso, Fixture};
+
+async fn status(fixture: &Fixture, profile_name: &str) -> Option<i32> {
+    let sso = FakeSso::start(|_| (500, String::new()));
+
+    let output = fixture
+        .run(&sso.url(), &["check", "--status-only", profile_name])
+        .await;
+
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+    assert!(sso.requests().is_empty());
+
+    output.status.code()
+}
+
+#[tokio::test]
+async fn status_of_valid_token_is_0() {
+    let fixture = Fixture::new();
+
+    assert_eq!(status(&fixture, "dev").await, Some(0));
+}
+
+#[tokio::test]
+async fn status_of_expired_token_is_10() {
+    let fixture = Fixture::new();
+
+    fixture.write_token("2020-01-01T00:00:00Z");
+
+    assert_eq!(status(&fixture, "dev").await, Some(10));
+}
+
+#[tokio::test]
+async fn status_of_missing_token_is_11() {
+    let fixture = Fixture::new();
+
+    std::fs::remove_dir_all(fixture.cache_dir()).unwrap();
+
+    assert_eq!(status(&fixture, "dev").await, Some(11));
+}
+
+#[tokio::test]
+async fn reports_unreadable_token() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_token("next tuesday");
+
+    let output = fixture.run(&sso.url(), &["check", "dev"]).await;
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "profile ok\ntoken unreadable\n");
+    assert_eq!(status(&fixture, "dev").await, Some(11));
+}
+
+#[tokio::test]
+async fn rejects_token_cached_for_another_start_url() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    // the file the `example` session's token is cached in, holding a token for another instance
+    let cache_file = std::fs::read_dir(fixture.cache_dir())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+
+    std::fs::write(
+        &cache_file,
+        r#"{"accessToken":"other-access-token","expiresAt":"2030-01-01T00:00:00Z","region":"us-east-1","startUrl":"https://other.awsapps.com/start"}"#,
+    )
+    .unwrap();
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["check", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "profile ok\ntoken unreadable\n");
+    assert!(
+        stderr(&output).contains("issued for start url 'https://other.awsapps.com/start'"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn accepts_token_cached_with_a_trailing_slash() {
+    let fixture = Fixture::new();
+
+    let cache_file = std::fs::read_dir(fixture.cache_dir())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let token = std::fs::read_to_string(&cache_file).unwrap();
+
+    std::fs::write(&cache_file, token.replace("/start\"", "/start/\"")).unwrap();
+
+    assert_eq!(status(&fixture, "dev").await, Some(0));
+}
+
+#[tokio::test]
+async fn status_of_missing_profile_is_2() {
+    let fixture = Fixture::new();
+
+    assert_eq!(status(&fixture, "nonexistent").await, Some(2));
+}
+
+#[tokio::test]
+async fn debug_cache_describes_lookup_without_the_token() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--debug-cache", "check", "dev"])
+        .await;
+    let stderr = stderr(&output);
+
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("(exists)"), "{}", stderr);
+    assert!(
+        stderr.contains("cache key 'example': ")
+            && stderr.contains("c3499c2729730a7f807efb8676a92dcb6f8a3f8f.json (exists)"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("cache key 'https://example.awsapps.com/start/': "));
+    assert!(
+        stderr.contains("expires at 2030-01-01T00:00:00Z"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("test-access-token"));
+}
+
+/// Add a `portal` profile to the fixture's configuration carrying nothing but its sso-session.
+fn add_session_only_profile(fixture: &Fixture) {
+    let config = std::fs::read_to_string(fixture.path(".aws/config")).unwrap();
+
+    fixture.write(
+        ".aws/config",
+        &format!("{}\n[profile portal]\nsso_session = example\n", config),
+    );
+}
+
+#[tokio::test]
+async fn checks_profile_without_account_or_role() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    add_session_only_profile(&fixture);
+
+    let output = fixture.run(&sso.url(), &["check", "portal"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(status(&fixture, "portal").await, Some(0));
+}
+
+#[tokio::test]
+async fn fetching_credentials_requires_account_and_role() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    add_session_only_profile(&fixture);
+
+    let output = fixture.run(&sso.url(), &["portal"]).await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("profile must have sso_account_id property set"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
@@ -0,0 +1,368 @@
+//! A fake SSO portal API and a scratch AWS directory for running `aws-sso-env` end to end.
+
+#![allow(dead_code)]
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::Output;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::process::Command;
+
+/// The SSO start URL that the fixture's profiles and token share.
+pub const START_URL: &str = "https://example.awsapps.com/start";
+
+/// The `expiration` of the role credentials in [`role_credentials`] responses.
+pub const EXPIRATION: i64 = 1_893_456_000_000;
+
+/// A request received by the [`FakeSso`] server.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    /// The path and query string.
+    pub path: String,
+    /// The value of the `x-amz-sso_bearer_token` header.
+    pub bearer_token: Option<String>,
+}
+
+type Responder = dyn Fn(usize, &RecordedRequest) -> (u16, String) + Send + Sync;
+
+/// A local HTTP server standing in for the SSO portal API, answering every request with the status and body returned
+/// by its responder for the zero-based index of the request.
+pub struct FakeSso {
+    pub addr: SocketAddr,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    date: Arc<Mutex<Option<String>>>,
+    connections: Arc<AtomicUsize>,
+    connection_delay: Arc<Mutex<Duration>>,
+}
+
+impl FakeSso {
+    /// Start the server on an ephemeral port on the current Tokio runtime.
+    pub fn start<F>(responder: F) -> Self
+    where
+        F: Fn(usize) -> (u16, String) + Send + Sync + 'static,
+    {
+        Self::start_with(Arc::new(move |index, _: &RecordedRequest| responder(index)))
+    }
+
+    /// Start the server, answering every request with the credentials `ASIADEV`, `dev-secret`, and `dev-session`.
+    pub fn with_dev_credentials() -> Self {
+        Self::start(|_| {
+            (
+                200,
+                role_credentials("ASIADEV", "dev-secret", "dev-session"),
+            )
+        })
+    }
+
+    /// Start the server, answering every request with the status and body returned by the responder for its path, for
+    /// requests made concurrently in no particular order.
+    pub fn start_routed<F>(responder: F) -> Self
+    where
+        F: Fn(&str) -> (u16, String) + Send + Sync + 'static,
+    {
+        Self::start_with(Arc::new(move |_, request: &RecordedRequest| {
+            responder(request.path.as_str())
+        }))
+    }
+
+    fn start_with(responder: Arc<Responder>) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let count = Arc::new(AtomicUsize::new(0));
+        let date: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+        let connections = Arc::new(AtomicUsize::new(0));
+        let connection_delay = Arc::new(Mutex::new(Duration::ZERO));
+
+        let recorded = requests.clone();
+        let dated = date.clone();
+        let connected = connections.clone();
+        let delayed = connection_delay.clone();
+
+        let make_service = make_service_fn(move |_| {
+            connected.fetch_add(1, Ordering::SeqCst);
+
+            let delay = *delayed.lock().unwrap();
+
+            let responder = responder.clone();
+            let recorded = recorded.clone();
+            let count = count.clone();
+            let dated = dated.clone();
+
+            async move {
+                tokio::time::sleep(delay).await;
+
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let index = count.fetch_add(1, Ordering::SeqCst);
+
+                    let request = RecordedRequest {
+                        path: request
+                            .uri()
+                            .path_and_query()
+                            .map(|p| p.to_string())
+                            .unwrap_or_default(),
+                        bearer_token: request
+                            .headers()
+                            .get("x-amz-sso_bearer_token")
+                            .and_then(|v| v.to_str().ok())
+                            .map(String::from),
+                    };
+
+                    let (status, body) = responder(index, &request);
+
+                    recorded.lock().unwrap().push(request);
+
+                    let mut response = Response::builder()
+                        .status(status)
+                        .header("content-type", "application/json");
+
+                    // otherwise hyper adds the current time
+                    if let Some(date) = dated.lock().unwrap().as_deref() {
+                        response = response.header("date", date);
+                    }
+
+                    let response = response.body(Body::from(body)).unwrap();
+
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+
+        tokio::spawn(server);
+
+        Self {
+            addr,
+            requests,
+            date,
+            connections,
+            connection_delay,
+        }
+    }
+
+    /// Send the given `Date` header with every response from now on, as if the server's clock were set to that time.
+    pub fn set_date(&self, date: &str) {
+        *self.date.lock().unwrap() = Some(date.into());
+    }
+
+    /// Wait the given time before serving each new connection from now on, as setting up a TLS connection to the real
+    /// portal would take.
+    pub fn set_connection_delay(&self, delay: Duration) {
+        *self.connection_delay.lock().unwrap() = delay;
+    }
+
+    /// The URL to pass as `--sso-endpoint-url`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The number of connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// The requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// A successful `GetRoleCredentials` response body.
+pub fn role_credentials(
+    access_key_id: &str,
+    secret_access_key: &str,
+    session_token: &str,
+) -> String {
+    format!(
+        r#"{{"roleCredentials":{{"accessKeyId":"{}","secretAccessKey":"{}","sessionToken":"{}","expiration":{}}}}}"#,
+        access_key_id, secret_access_key, session_token, EXPIRATION
+    )
+}
+
+/// An error response body of the given type, as the SSO portal API sends them.
+pub fn error_body(error_type: &str, message: &str) -> String {
+    format!(r#"{{"__type":"{}","message":"{}"}}"#, error_type, message)
+}
+
+/// The name of the cache file of the token for the `example` session, the SHA1 of the session name.
+const TOKEN_FILENAME: &str = "c3499c2729730a7f807efb8676a92dcb6f8a3f8f.json";
+
+/// The file in which role credentials for the `dev` profile are cached: its name and the SHA1 of its start url, account,
+/// role, and name.
+const DEV_CREDENTIALS_FILENAME: &str =
+    "aws-sso-env-dev-cedfcab666fc102716520587f42008df69f784b7.json";
+
+/// The cache directory written to by `aws sso login`, relative to the home directory.
+const CACHE_DIR: &str = ".aws/sso/cache";
+
+/// A scratch home directory holding an AWS config file with SSO profiles and a cached SSO token, removed on drop.
+pub struct Fixture {
+    pub home: PathBuf,
+}
+
+impl Fixture {
+    /// Create the directory with `dev` and `prod` profiles and a token for [`START_URL`] valid until 2030.
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let home = std::env::temp_dir().join(format!(
+            "aws-sso-env-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let fixture = Self { home };
+
+        fixture.write(
+            ".aws/config",
+            &format!(
+                "[profile dev]\n\
+                 sso_session = example\n\
+                 sso_account_id = 123456789012\n\
+                 sso_role_name = Developer\n\
+                 region = us-east-1\n\
+                 \n\
+                 [profile prod]\n\
+                 sso_session = example\n\
+                 sso_account_id = 210987654321\n\
+                 sso_role_name = ReadOnly\n\
+                 region = eu-west-1\n\
+                 \n\
+                 [sso-session example]\n\
+                 sso_start_url = {}\n\
+                 sso_region = us-east-1\n",
+                START_URL
+            ),
+        );
+
+        fixture.write_token("2030-01-01T00:00:00Z");
+        fixture
+    }
+
+    /// The path of the file at the given path relative to the home directory.
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.home.join(relative)
+    }
+
+    /// Write the file at the given path relative to the home directory, creating its parents.
+    pub fn write(&self, relative: &str, contents: &str) {
+        let path = self.path(relative);
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// Replace the cached SSO token for the `example` session with one expiring at the given time.
+    pub fn write_token(&self, expires_at: &str) {
+        self.write_token_in(CACHE_DIR, "test-access-token", expires_at);
+    }
+
+    /// Write a token for the `example` session with the given access token and expiry to another cache directory,
+    /// relative to the home directory, as another SSO tool might.
+    pub fn write_token_in(&self, cache_dir: &str, access_token: &str, expires_at: &str) {
+        self.write(
+            &format!("{}/{}", cache_dir, TOKEN_FILENAME),
+            &format!(
+                r#"{{"accessToken":"{}","expiresAt":"{}","region":"us-east-1","startUrl":"{}"}}"#,
+                access_token, expires_at, START_URL
+            ),
+        );
+    }
+
+    /// Replace the cached SSO token for the `example` session with one expiring at the given time, along with a refresh
+    /// token and OIDC client as cached by the AWS CLI.
+    pub fn write_refreshable_token(&self, expires_at: &str) {
+        self.write(
+            &format!("{}/{}", CACHE_DIR, TOKEN_FILENAME),
+            &format!(
+                r#"{{"accessToken":"test-access-token","expiresAt":"{}","region":"us-east-1","startUrl":"{}","refreshToken":"test-refresh-token","clientId":"test-client-id","clientSecret":"test-client-secret","registrationExpiresAt":"2030-01-01T00:00:00Z"}}"#,
+                expires_at, START_URL
+            ),
+        );
+    }
+
+    /// The cached SSO token for the `example` session.
+    pub fn read_token(&self) -> serde_json::Value {
+        serde_json::from_str(
+            &std::fs::read_to_string(self.cache_dir().join(TOKEN_FILENAME)).unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// The file in which role credentials for the `dev` profile are cached.
+    pub fn dev_credentials_cache_file(&self) -> PathBuf {
+        self.cache_dir().join(DEV_CREDENTIALS_FILENAME)
+    }
+
+    /// Cache the given role credentials JSON for the `dev` profile's account and role.
+    pub fn write_dev_credentials(&self, credentials: &str) {
+        self.write(
+            &format!("{}/{}", CACHE_DIR, DEV_CREDENTIALS_FILENAME),
+            &format!(
+                r#"{{"accountId":"123456789012","roleName":"Developer","credentials":{}}}"#,
+                credentials
+            ),
+        );
+    }
+
+    /// The SSO cache directory within the home directory.
+    pub fn cache_dir(&self) -> PathBuf {
+        self.path(CACHE_DIR)
+    }
+
+    /// A command running `aws-sso-env` against this home directory and the given endpoint, with a clean environment.
+    pub fn command(&self, endpoint_url: &str) -> Command {
+        let mut command = self.logging_command(endpoint_url);
+
+        command.arg("-q");
+
+        command
+    }
+
+    /// Like [`Fixture::command`], but without `-q`, so that warnings are logged to standard error too.
+    pub fn logging_command(&self, endpoint_url: &str) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_aws-sso-env"));
+
+        command
+            .env_clear()
+            .env("HOME", &self.home)
+            .env("AWS_CONFIG_FILE", self.path(".aws/config"))
+            .env("AWS_SHARED_CREDENTIALS_FILE", self.path(".aws/credentials"))
+            .args(["--sso-endpoint-url", endpoint_url])
+            .args(["--oidc-endpoint-url", endpoint_url]);
+
+        command
+    }
+
+    /// Run `aws-sso-env` with the given arguments against the endpoint.
+    pub async fn run(&self, endpoint_url: &str, args: &[&str]) -> Output {
+        self.command(endpoint_url)
+            .args(args)
+            .output()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
+
+/// The standard output of the process as a string.
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// The standard error of the process as a string.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
@@ -0,0 +1,1943 @@
+mod common;
+
+use common::{error_body, role_credentials, stderr, stdout, FakeSso, Fixture};
+
+#[tokio::test]
+async fn exports_fetched_credentials() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+
+    assert!(
+        stdout.contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("export AWS_SECRET_ACCESS_KEY=dev-secret\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("export AWS_SESSION_TOKEN=dev-session\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("export AWS_DEFAULT_REGION=us-east-1\n"),
+        "{}",
+        stdout
+    );
+
+    let requests = sso.requests();
+
+    assert_eq!(requests.len(), 1);
+    assert!(
+        requests[0].path.starts_with("/federation/credentials?"),
+        "{}",
+        requests[0].path
+    );
+    assert!(
+        requests[0].path.contains("account_id=123456789012"),
+        "{}",
+        requests[0].path
+    );
+    assert!(
+        requests[0].path.contains("role_name=Developer"),
+        "{}",
+        requests[0].path
+    );
+    assert_eq!(
+        requests[0].bearer_token.as_deref(),
+        Some("test-access-token")
+    );
+}
+
+#[tokio::test]
+async fn exports_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIAJSON", "json-secret", "json-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["--json", "prod"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(json["accessKeyId"], "ASIAJSON");
+    assert_eq!(json["secretAccessKey"], "json-secret");
+    assert_eq!(json["sessionToken"], "json-session");
+    assert_eq!(json["region"], "eu-west-1");
+    assert_eq!(json["accountId"], "210987654321");
+    assert_eq!(json["roleName"], "ReadOnly");
+    assert_eq!(json["profileName"], "prod");
+}
+
+#[tokio::test]
+async fn exports_base64_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIAJSON", "json-secret", "json-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--format", "base64-json", "prod"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = stdout(&output);
+    let encoded = stdout.strip_suffix('\n').unwrap();
+
+    assert!(!encoded.contains(char::is_whitespace), "{}", stdout);
+
+    let decoded = aws_smithy_types::base64::decode(encoded).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&decoded).unwrap();
+
+    assert_eq!(json["accessKeyId"], "ASIAJSON");
+    assert_eq!(json["secretAccessKey"], "json-secret");
+    assert_eq!(json["sessionToken"], "json-session");
+    assert_eq!(json["expiresAt"], "2030-01-01T00:00:00Z");
+    assert_eq!(json["accountId"], "210987654321");
+    assert_eq!(json["profileName"], "prod");
+}
+
+#[tokio::test]
+async fn unauthorized_token_exits_with_code_5() {
+    let sso = FakeSso::start(|_| {
+        (
+            401,
+            error_body(
+                "UnauthorizedException",
+                "Session token not found or invalid",
+            ),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+}
+
+#[tokio::test]
+async fn unreadable_token_exits_with_code_4() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_token("next tuesday");
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+    assert!(
+        stderr(&output).contains("could not be read: token expiry 'next tuesday'"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let sso = FakeSso::start(|index| match index {
+        0 => (500, error_body("InternalServerException", "try again")),
+        _ => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    assert_eq!(sso.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn prints_env_names_without_fetching() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--print-env-names", "--no-region", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "AWS_ACCESS_KEY_ID\nAWS_SECRET_ACCESS_KEY\nAWS_SESSION_TOKEN\nAWS_CREDENTIAL_EXPIRATION\n"
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn unset_clears_every_exported_variable() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    // every profile sets an output format, so that every variable is exported
+    let config = std::fs::read_to_string(fixture.path(".aws/config")).unwrap();
+    fixture.write(
+        ".aws/config",
+        &config.replace("\nregion = ", "\noutput = json\nregion = "),
+    );
+
+    let exported = fixture.run(&sso.url(), &["dev", "prod"]).await;
+    assert!(exported.status.success(), "{}", stderr(&exported));
+
+    let unset = fixture.run(&sso.url(), &["unset", "dev", "prod"]).await;
+    assert!(unset.status.success(), "{}", stderr(&unset));
+
+    let exported_names: Vec<String> = stdout(&exported)
+        .lines()
+        .filter_map(|line| line.strip_prefix("export "))
+        .map(|line| line.split('=').next().unwrap().to_string())
+        .collect();
+
+    assert_eq!(
+        stdout(&unset),
+        format!("unset {}\n", exported_names.join(" "))
+    );
+}
+
+#[tokio::test]
+async fn overrides_account_and_role() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIAOPS", "ops-secret", "ops-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--json",
+                "--account-id",
+                "111122223333",
+                "--role-name",
+                "Operator",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(json["accountId"], "111122223333");
+    assert_eq!(json["roleName"], "Operator");
+    assert_eq!(
+        sso.requests()[0].path,
+        "/federation/credentials?role_name=Operator&account_id=111122223333"
+    );
+
+    // credentials for the overridden role must never be mistaken for the profile's own
+    assert!(!fixture.dev_credentials_cache_file().exists());
+}
+
+#[tokio::test]
+async fn rejects_malformed_account_id_override() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIAOPS", "ops-secret", "ops-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--account-id", "1111", "dev"])
+        .await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("not a 12-digit account id"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn offline_reuses_cached_credentials() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_dev_credentials(
+        r#"{"accessKeyId":"ASIACACHED","secretAccessKey":"cached-secret","sessionToken":"cached-session","expiresAt":"2030-01-01T00:00:00Z"}"#,
+    );
+
+    let output = fixture.run(&sso.url(), &["--offline", "dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIACACHED\n"));
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn never_reuses_credentials_cached_for_another_account() {
+    let sso = FakeSso::start_routed(|path| {
+        let account_id = path.rsplit('=').next().unwrap().to_string();
+
+        (
+            200,
+            role_credentials(&format!("ASIA{}", account_id), "secret", "session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIA123456789012\n"));
+
+    // editing the profile's account must not reuse the credentials cached for the old one
+    let config = std::fs::read_to_string(fixture.path(".aws/config")).unwrap();
+    fixture.write(
+        ".aws/config",
+        &config.replacen("123456789012", "111111111111", 1),
+    );
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIA111111111111\n"));
+    assert_eq!(sso.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn ignores_cached_credentials_for_another_role() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    std::fs::write(
+        fixture.dev_credentials_cache_file(),
+        r#"{"accountId":"123456789012","roleName":"Admin","credentials":{"accessKeyId":"ASIACACHED","secretAccessKey":"cached-secret","sessionToken":"cached-session","expiresAt":"2030-01-01T00:00:00Z"}}"#,
+    )
+    .unwrap();
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn caches_credentials_privately_over_a_readable_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+    let path = fixture.dev_credentials_cache_file();
+
+    // expired, so they're fetched again and cached over the readable file
+    fixture.write_dev_credentials(
+        r#"{"accessKeyId":"ASIASTALE","secretAccessKey":"stale-secret","sessionToken":"stale-session","expiresAt":"2020-01-01T00:00:00Z"}"#,
+    );
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sso.requests().len(), 1);
+    assert!(std::fs::read_to_string(&path).unwrap().contains("ASIADEV"));
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+}
+
+#[tokio::test]
+async fn offline_fails_without_cached_credentials() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["--offline", "dev"]).await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--offline"), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn exports_several_profiles_in_the_order_given() {
+    let sso = FakeSso::start_routed(|path| match path.contains("account_id=123456789012") {
+        true => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+        false => (
+            200,
+            role_credentials("ASIAPROD", "prod-secret", "prod-session"),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--max-concurrency", "2", "prod", "dev"])
+        .await;
+    let stdout = stdout(&output);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sso.requests().len(), 2);
+
+    let prod = stdout
+        .find("export AWS_ACCESS_KEY_ID_PROD=ASIAPROD\n")
+        .unwrap();
+    let dev = stdout
+        .find("export AWS_ACCESS_KEY_ID_DEV=ASIADEV\n")
+        .unwrap();
+
+    assert!(prod < dev, "{}", stdout);
+}
+
+#[tokio::test]
+async fn profiles_in_the_same_region_share_a_connection() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    // one at a time, so that the connection is free to be reused, as a client per profile would need one each
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--max-concurrency", "1", "--no-cache", "dev", "prod"],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sso.requests().len(), 2);
+    assert_eq!(sso.connections(), 1);
+}
+
+#[tokio::test]
+async fn sharing_a_client_saves_setting_up_a_connection_per_profile() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+    let delay = std::time::Duration::from_millis(200);
+
+    let config = std::fs::read_to_string(fixture.path(".aws/config")).unwrap();
+    fixture.write(
+        ".aws/config",
+        &format!(
+            "{}\n\
+             [profile qa]\n\
+             sso_session = example\n\
+             sso_account_id = 333333333333\n\
+             sso_role_name = Developer\n\
+             \n\
+             [profile staging]\n\
+             sso_session = example\n\
+             sso_account_id = 444444444444\n\
+             sso_role_name = Developer\n",
+            config
+        ),
+    );
+
+    let profiles = ["dev", "prod", "qa", "staging"];
+
+    // each connection is as slow to set up as a TLS handshake with the real portal might be
+    sso.set_connection_delay(delay);
+
+    let started = std::time::Instant::now();
+
+    for profile_name in profiles {
+        let output = fixture.run(&sso.url(), &["--no-cache", profile_name]).await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let separately = started.elapsed();
+    let started = std::time::Instant::now();
+
+    let mut args = vec!["--max-concurrency", "1", "--no-cache"];
+    args.extend(profiles);
+
+    let output = fixture.run(&sso.url(), &args).await;
+
+    let shared = started.elapsed();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sso.connections(), profiles.len() + 1);
+
+    println!(
+        "{} profiles with a {:?} connection setup: {:?} separately, {:?} sharing a client",
+        profiles.len(),
+        delay,
+        separately,
+        shared
+    );
+
+    // separately, every profile but one pays for a connection of its own
+    assert!(
+        shared + delay * 2 < separately,
+        "{:?} sharing, {:?} separately",
+        shared,
+        separately
+    );
+}
+
+#[tokio::test]
+async fn exports_remaining_profiles_when_one_fails() {
+    let sso = FakeSso::start_routed(|path| match path.contains("account_id=123456789012") {
+        true => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+        false => (
+            401,
+            error_body(
+                "UnauthorizedException",
+                "Session token not found or invalid",
+            ),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["prod", "dev"]).await;
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID_DEV=ASIADEV\n"));
+    assert!(stderr(&output).contains("prod: "), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn rejects_zero_concurrency() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--max-concurrency", "0", "dev", "prod"])
+        .await;
+
+    assert!(!output.status.success());
+    assert!(sso.requests().is_empty());
+}
+
+/// Cache credentials for the `dev` profile expiring the given number of minutes from now.
+fn cache_credentials_expiring_in(fixture: &Fixture, minutes: i64) {
+    let expires_at = time::OffsetDateTime::now_utc() + time::Duration::minutes(minutes);
+
+    fixture.write_dev_credentials(&format!(
+            r#"{{"accessKeyId":"ASIACACHED","secretAccessKey":"cached-secret","sessionToken":"cached-session","expiresAt":"{}"}}"#,
+            expires_at
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap()
+    ));
+}
+
+#[tokio::test]
+async fn warns_when_credentials_expire_soon() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    cache_credentials_expiring_in(&fixture, 10);
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["--offline", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Credentials for profile 'dev' expire in 9 minute(s)"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stdout(&output).contains("minute(s)"));
+}
+
+#[tokio::test]
+async fn warns_only_within_warn_before() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    cache_credentials_expiring_in(&fixture, 10);
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["--offline", "--warn-before", "5", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        !stderr(&output).contains("expire in"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+/// The output of a `credential_process`, as the AWS SDKs parse it: unknown keys are rejected here so that nothing but
+/// the documented schema is printed.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CredentialProcess {
+    #[serde(rename = "Version")]
+    version: u8,
+    #[serde(rename = "AccessKeyId")]
+    access_key_id: String,
+    #[serde(rename = "SecretAccessKey")]
+    secret_access_key: String,
+    #[serde(rename = "SessionToken")]
+    session_token: String,
+    #[serde(rename = "Expiration")]
+    expiration: String,
+}
+
+#[tokio::test]
+async fn exports_credential_process_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--format", "json-aws", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: CredentialProcess = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(json.version, 1);
+    assert_eq!(json.access_key_id, "ASIADEV");
+    assert_eq!(json.secret_access_key, "dev-secret");
+    assert_eq!(json.session_token, "dev-session");
+    assert!(time::OffsetDateTime::parse(
+        json.expiration.as_str(),
+        &time::format_description::well_known::Rfc3339
+    )
+    .is_ok());
+}
+
+/// A successful STS `AssumeRole` response body.
+fn assumed_role_credentials(access_key_id: &str) -> String {
+    format!(
+        "<AssumeRoleResponse xmlns=\"https://sts.amazonaws.com/doc/2011-06-15/\"><AssumeRoleResult><Credentials>\
+         <AccessKeyId>{}</AccessKeyId><SecretAccessKey>assumed-secret</SecretAccessKey>\
+         <SessionToken>assumed-session</SessionToken><Expiration>2030-01-01T00:00:00Z</Expiration></Credentials>\
+         </AssumeRoleResult></AssumeRoleResponse>",
+        access_key_id
+    )
+}
+
+#[tokio::test]
+async fn exports_assumed_role_credentials() {
+    let sso = FakeSso::start_routed(|path| match path {
+        "/" => (200, assumed_role_credentials("ASIAASSUMED")),
+        _ => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--json",
+                "--sts-endpoint-url",
+                &sso.url(),
+                "--assume-role",
+                "arn:aws:iam::111122223333:role/ci/Deployer",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(json["accessKeyId"], "ASIAASSUMED");
+    assert_eq!(json["sessionToken"], "assumed-session");
+    assert_eq!(json["accountId"], "111122223333");
+    assert_eq!(json["roleName"], "Deployer");
+    assert_eq!(sso.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn assume_role_failure_exits_with_code_6() {
+    let sso = FakeSso::start_routed(|path| match path {
+        "/" => (
+            403,
+            "<ErrorResponse><Error><Type>Sender</Type><Code>AccessDenied</Code>\
+             <Message>not authorized</Message></Error></ErrorResponse>"
+                .into(),
+        ),
+        _ => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--sts-endpoint-url",
+                &sso.url(),
+                "--assume-role",
+                "arn:aws:iam::111122223333:role/Deployer",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert_eq!(output.status.code(), Some(6), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+}
+
+#[tokio::test]
+async fn rejects_malformed_role_arn() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--assume-role", "arn:aws:s3:::bucket", "dev"])
+        .await;
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("role arn"), "{}", stderr(&output));
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn assume_role_with_mfa_requires_token_code() {
+    let sso = FakeSso::start_routed(|path| match path {
+        "/" => (200, assumed_role_credentials("ASIAASSUMED")),
+        _ => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--sts-endpoint-url",
+                &sso.url(),
+                "--assume-role",
+                "arn:aws:iam::111122223333:role/Deployer",
+                "--mfa-serial",
+                "arn:aws:iam::123456789012:mfa/alice",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("--token-code"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().iter().all(|request| request.path != "/"));
+}
+
+#[tokio::test]
+async fn endpoint_url_takes_precedence_over_dualstack() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("AWS_USE_DUALSTACK_ENDPOINT", "true")
+        .args(["--dualstack", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn times_out_unresponsive_sso() {
+    // connections are queued by the listener but never answered
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&url, &["--timeout", "1", "--max-retries", "0", "dev"])
+        .await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("SSO GetRoleCredentials request timed out after 1 seconds"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stdout(&output).is_empty());
+}
+
+#[tokio::test]
+async fn notes_role_arn_unless_comments_are_turned_off() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["--no-cache", "dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).starts_with(
+            "# role arn: arn:aws:iam::123456789012:role/Developer\n# expires at 2030-01-01T00:00:00Z\n"
+        ),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = fixture
+        .run(&sso.url(), &["--no-cache", "--no-comments", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).starts_with("export AWS_ACCESS_KEY_ID=ASIADEV\n"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[tokio::test]
+async fn no_comments_applies_to_every_shell_format() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    for format in ["bash", "fish", "powershell", "cmd", "dotenv"] {
+        let output = fixture
+            .run(&sso.url(), &["--format", format, "--no-comments", "dev"])
+            .await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let stdout = stdout(&output);
+
+        assert_eq!(stdout.lines().count(), 6, "{}", stdout);
+        assert!(
+            stdout
+                .lines()
+                .all(|line| !line.starts_with('#') && !line.starts_with("REM ")),
+            "{}",
+            stdout
+        );
+    }
+}
+
+#[tokio::test]
+async fn passes_profile_properties_through() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let config = std::fs::read_to_string(fixture.path(".aws/config")).unwrap();
+    fixture.write(
+        ".aws/config",
+        &config.replace(
+            "region = us-east-1\n",
+            "region = us-east-1\noutput = json\ncli_pager =\n",
+        ),
+    );
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--passthrough-key",
+                "cli_pager=AWS_PAGER",
+                "--passthrough-key",
+                "missing=AWS_MISSING",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).ends_with(
+            "export AWS_REGION=us-east-1\nexport AWS_DEFAULT_OUTPUT=json\nexport AWS_PAGER=\n"
+        ),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = fixture
+        .run(&sso.url(), &["--passthrough-key", "cli_pager", "dev"])
+        .await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("expected key=VARIABLE"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn logs_json() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["--log-format", "json", "-v", "dev"])
+        .output()
+        .await
+        .unwrap();
+    let stderr = stderr(&output);
+
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!stderr.is_empty());
+
+    for line in stderr.lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+
+        for field in ["timestamp", "level", "module", "message"] {
+            assert!(record[field].is_string(), "{}", line);
+        }
+    }
+}
+
+#[tokio::test]
+async fn colors_logs_only_when_asked_to() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    // standard error isn't a terminal here, so `auto` is plain
+    for (log_style, args, colored) in [
+        (None, &[][..], false),
+        (None, &["--color", "auto"][..], false),
+        (None, &["--color", "always"][..], true),
+        (None, &["--color", "never"][..], false),
+        (Some("always"), &[][..], true),
+        (Some("always"), &["--color", "never"][..], false),
+        (Some("always"), &["--no-color"][..], false),
+    ] {
+        let mut command = fixture.logging_command(&sso.url());
+
+        if let Some(log_style) = log_style {
+            command.env("RUST_LOG_STYLE", log_style);
+        }
+
+        let output = command.args(args).arg("dev").output().await.unwrap();
+        let stderr = stderr(&output);
+
+        assert!(output.status.success(), "{}", stderr);
+        assert!(!stderr.is_empty());
+        assert_eq!(stderr.contains('\x1b'), colored, "{:?}: {}", args, stderr);
+    }
+}
+
+#[tokio::test]
+async fn never_logs_secrets() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    for log_format in ["text", "json"] {
+        let output = fixture
+            .logging_command(&sso.url())
+            .env("RUST_LOG", "trace")
+            .args(["--log-format", log_format, "-vv", "--refresh", "dev"])
+            .output()
+            .await
+            .unwrap();
+        let stderr = stderr(&output);
+
+        assert!(output.status.success(), "{}", stderr);
+
+        for secret in ["test-access-token", "dev-secret", "dev-session"] {
+            assert!(!stderr.contains(secret), "{}", stderr);
+        }
+    }
+}
+
+#[tokio::test]
+async fn searches_token_cache_dirs_in_order_for_an_unexpired_token() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write_token("2020-01-01T00:00:00Z");
+    fixture.write_token_in("vault", "vault-access-token", "2020-06-01T00:00:00Z");
+    fixture.write_token_in("leapp", "leapp-access-token", "2030-01-01T00:00:00Z");
+    fixture.write_token_in("other", "other-access-token", "2030-06-01T00:00:00Z");
+
+    let output = fixture
+        .command(&sso.url())
+        .arg("--no-cache")
+        .args(["--token-cache-dir", fixture.path("vault").to_str().unwrap()])
+        .args(["--token-cache-dir", fixture.path("leapp").to_str().unwrap()])
+        .args(["--token-cache-dir", fixture.path("other").to_str().unwrap()])
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sso.requests()[0].bearer_token.as_deref(),
+        Some("leapp-access-token")
+    );
+}
+
+#[tokio::test]
+async fn prefers_the_cache_dir_over_token_cache_dirs() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write_token_in("leapp", "leapp-access-token", "2030-06-01T00:00:00Z");
+
+    let output = fixture
+        .command(&sso.url())
+        .args(["--token-cache-dir", fixture.path("leapp").to_str().unwrap()])
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sso.requests()[0].bearer_token.as_deref(),
+        Some("test-access-token")
+    );
+}
+
+/// The number of seconds from now until the Unix timestamp.
+fn seconds_until(timestamp: i64) -> i64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    timestamp - now
+}
+
+#[tokio::test]
+async fn prints_seconds_until_token_or_credentials_expire() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write_token("2029-01-01T00:00:00Z");
+
+    let token = fixture
+        .run(&sso.url(), &["--expires-in", "token", "dev"])
+        .await;
+
+    assert!(token.status.success(), "{}", stderr(&token));
+    assert!(sso.requests().is_empty());
+
+    let seconds: i64 = stdout(&token).trim_end().parse().unwrap();
+    assert!(
+        (seconds - seconds_until(1_861_920_000)).abs() <= 5,
+        "{}",
+        seconds
+    );
+
+    let credentials = fixture
+        .run(&sso.url(), &["--expires-in", "credentials", "dev"])
+        .await;
+
+    assert!(credentials.status.success(), "{}", stderr(&credentials));
+
+    let seconds: i64 = stdout(&credentials).trim_end().parse().unwrap();
+    assert!(
+        (seconds - seconds_until(1_893_456_000)).abs() <= 5,
+        "{}",
+        seconds
+    );
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn prints_expired_token_as_zero_unless_negative_allowed() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_token("2020-01-01T00:00:00Z");
+
+    let clamped = fixture
+        .run(&sso.url(), &["--expires-in", "token", "dev"])
+        .await;
+
+    assert!(clamped.status.success(), "{}", stderr(&clamped));
+    assert_eq!(stdout(&clamped), "0\n");
+
+    let negative = fixture
+        .run(
+            &sso.url(),
+            &["--expires-in", "token", "--allow-negative", "dev"],
+        )
+        .await;
+
+    assert!(negative.status.success(), "{}", stderr(&negative));
+
+    let seconds: i64 = stdout(&negative).trim_end().parse().unwrap();
+    assert!(
+        (seconds - seconds_until(1_577_836_800)).abs() <= 5,
+        "{}",
+        seconds
+    );
+}
+
+#[tokio::test]
+async fn dry_run_reports_what_would_be_fetched_without_calling_sso() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--dry-run", "--role-name", "Admin", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "dry run: cached SSO token for profile 'dev' is valid until 2030-01-01T00:00:00Z\n\
+         dry run: would fetch credentials for account 123456789012 role Admin\n"
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn dry_run_never_refreshes_expired_tokens() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_refreshable_token("2020-01-01T00:00:00Z");
+
+    let output = fixture.run(&sso.url(), &["--dry-run", "dev"]).await;
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn env_formats_match_aws_configure_export_credentials() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    for (format, prefix) in [("env", "export "), ("env-no-export", "")] {
+        let output = fixture
+            .run(
+                &sso.url(),
+                &[
+                    "--format",
+                    format,
+                    "--passthrough-key",
+                    "region=REGION",
+                    "dev",
+                ],
+            )
+            .await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let stdout = stdout(&output);
+        let lines: Vec<&str> = stdout.lines().collect();
+
+        assert_eq!(lines.len(), 4, "{}", stdout);
+        assert_eq!(lines[0], format!("{}AWS_ACCESS_KEY_ID=ASIADEV", prefix));
+        assert_eq!(
+            lines[1],
+            format!("{}AWS_SECRET_ACCESS_KEY=dev-secret", prefix)
+        );
+        assert_eq!(lines[2], format!("{}AWS_SESSION_TOKEN=dev-session", prefix));
+        assert_eq!(
+            lines[3],
+            format!("{}AWS_CREDENTIAL_EXPIRATION=2030-01-01T00:00:00Z", prefix)
+        );
+
+        let names = fixture
+            .run(
+                &sso.url(),
+                &["--format", format, "--print-env-names", "dev"],
+            )
+            .await;
+
+        assert_eq!(
+            common::stdout(&names),
+            "AWS_ACCESS_KEY_ID\nAWS_SECRET_ACCESS_KEY\nAWS_SESSION_TOKEN\nAWS_CREDENTIAL_EXPIRATION\n"
+        );
+    }
+}
+
+#[tokio::test]
+async fn watch_requires_a_file_to_write() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["--watch", "dev"]).await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("--watch requires --output or --write-profile"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn watch_writes_credentials_until_interrupted() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+    let path = fixture.path("credentials.env");
+
+    let mut child = fixture
+        .command(&sso.url())
+        .args(["--watch", "--format", "dotenv", "--output"])
+        .arg(&path)
+        .arg("dev")
+        .spawn()
+        .unwrap();
+
+    for _ in 0..100 {
+        if path.exists() {
+            break;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .contains("AWS_ACCESS_KEY_ID=ASIADEV"),);
+
+    // still running, as the credentials don't expire for years
+    assert!(child.try_wait().unwrap().is_none());
+
+    let killed = std::process::Command::new("kill")
+        .args(["-INT", child.id().unwrap().to_string().as_str()])
+        .status()
+        .unwrap();
+
+    assert!(killed.success());
+    assert!(child.wait().await.unwrap().success());
+    assert_eq!(sso.requests().len(), 1);
+}
+
+/// A session token made of everything a shell might interpret, as it appears within a JSON string.
+const ADVERSARIAL_TOKEN_JSON: &str = r#"it's \"$HOME\" `id` $(id) \\ ‘x’ “y”\nline"#;
+
+/// [`ADVERSARIAL_TOKEN_JSON`] once decoded.
+const ADVERSARIAL_TOKEN: &str = "it's \"$HOME\" `id` $(id) \\ ‘x’ “y”\nline";
+
+#[tokio::test]
+async fn quotes_values_so_that_output_is_safe_to_eval() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", ADVERSARIAL_TOKEN_JSON),
+        )
+    });
+    let fixture = Fixture::new();
+
+    for format in ["bash", "env"] {
+        let output = fixture
+            .run(&sso.url(), &["--format", format, "--refresh", "dev"])
+            .await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let evaluated = std::process::Command::new("bash")
+            .env_clear()
+            .args([
+                "-c",
+                r#"eval "$1"; printf '%s' "$AWS_SESSION_TOKEN""#,
+                "bash",
+                stdout(&output).as_str(),
+            ])
+            .output()
+            .unwrap();
+
+        assert!(evaluated.status.success(), "{}", stderr(&evaluated));
+        assert_eq!(stdout(&evaluated), ADVERSARIAL_TOKEN);
+        assert!(stdout(&output).contains("AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    }
+
+    let fish = fixture
+        .run(&sso.url(), &["--format", "fish", "--refresh", "dev"])
+        .await;
+
+    assert!(stdout(&fish)
+        .contains("set -gx AWS_SESSION_TOKEN 'it\\'s \"$HOME\" `id` $(id) \\\\ ‘x’ “y”\nline'\n"));
+
+    let powershell = fixture
+        .run(&sso.url(), &["--format", "powershell", "--refresh", "dev"])
+        .await;
+
+    assert!(stdout(&powershell).contains(
+        "$env:AWS_SESSION_TOKEN = \"it's `\"`$HOME`\" ``id`` `$(id) \\ ‘x’ `“y`”\nline\"\n"
+    ));
+}
+
+#[tokio::test]
+async fn warns_when_the_clock_is_skewed() {
+    let sso = FakeSso::start(|_| {
+        (
+            401,
+            error_body(
+                "UnauthorizedException",
+                "Session token not found or invalid",
+            ),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(
+        !stderr(&output).contains("local clock"),
+        "{}",
+        stderr(&output)
+    );
+
+    // as if the token had really expired in 2031, but the local clock is behind
+    sso.set_date("Wed, 01 Jan 2031 00:00:00 GMT");
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("The local clock is") && stderr(&output).contains("behind AWS's"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn resolves_the_profile_from_aws_sso_env_profile_then_aws_profile() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let account = |env: &[(&str, &str)], args: &[&str]| {
+        let mut command = fixture.command(&sso.url());
+
+        command
+            .envs(env.iter().copied())
+            .arg("--dry-run")
+            .args(args);
+
+        async move {
+            let output = command.output().await.unwrap();
+
+            match output.status.success() {
+                true => stdout(&output)
+                    .split_whitespace()
+                    .find(|word| word.len() == 12 && word.bytes().all(|b| b.is_ascii_digit()))
+                    .unwrap()
+                    .to_string(),
+                false => stderr(&output),
+            }
+        }
+    };
+
+    let both = [("AWS_SSO_ENV_PROFILE", "dev"), ("AWS_PROFILE", "prod")];
+
+    assert_eq!(account(&both, &[]).await, "123456789012");
+    assert_eq!(account(&both, &["prod"]).await, "210987654321");
+    assert_eq!(account(&both, &["--profile", "prod"]).await, "210987654321");
+    assert_eq!(
+        account(&[("AWS_PROFILE", "prod")], &[]).await,
+        "210987654321"
+    );
+    assert_eq!(
+        account(&[("AWS_SSO_ENV_PROFILE", ""), ("AWS_PROFILE", "prod")], &[]).await,
+        "210987654321"
+    );
+    assert!(account(&[], &[]).await.contains(
+        "neither the AWS_SSO_ENV_PROFILE nor the AWS_PROFILE environment variable is set"
+    ));
+}
+
+#[tokio::test]
+async fn exports_the_expiration_under_each_name_given() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--expiration-var-name",
+                "AWS_CREDENTIAL_EXPIRATION",
+                "--expiration-var-name",
+                "AWS_SESSION_EXPIRATION",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(
+        "export AWS_CREDENTIAL_EXPIRATION=2030-01-01T00:00:00Z\n\
+         export AWS_SESSION_EXPIRATION=2030-01-01T00:00:00Z\n"
+    ));
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--expiration-var-name", "AWS_SESSION_EXPIRATION", "dev"],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_SESSION_EXPIRATION=2030-01-01T00:00:00Z\n"));
+    assert!(!stdout(&output).contains("AWS_CREDENTIAL_EXPIRATION"));
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--expiration-var-name",
+                "AWS_SESSION_EXPIRATION",
+                "--print-env-names",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(
+        stdout(&output).contains("AWS_SESSION_TOKEN\nAWS_SESSION_EXPIRATION\nAWS_DEFAULT_REGION\n")
+    );
+
+    let output = fixture
+        .run(&sso.url(), &["--expiration-var-name", "AWS-EXPIRY", "dev"])
+        .await;
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("'AWS-EXPIRY' is not a valid variable name"));
+}
+
+#[tokio::test]
+async fn prints_timings_to_stderr_only() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--timings", "--no-cache", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("phase="), "{}", stdout(&output));
+
+    let stderr = stderr(&output);
+    let phases: Vec<&str> = stderr
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split(' ').collect();
+
+            assert_eq!(fields.len(), 3, "{}", line);
+            assert_eq!(fields[1], "profile=dev");
+            assert!(fields[2]
+                .strip_prefix("ms=")
+                .unwrap()
+                .parse::<u64>()
+                .is_ok());
+
+            fields[0].strip_prefix("phase=").unwrap()
+        })
+        .collect();
+
+    assert_eq!(
+        phases,
+        ["profile", "token", "credentials-cache", "sso", "total"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn writes_credentials_to_the_given_file_descriptor() {
+    use std::os::unix::io::AsRawFd;
+
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let file = std::fs::File::create(fixture.path("fd-output")).unwrap();
+    let fd = file.as_raw_fd();
+
+    let mut command = fixture.command(&sso.url());
+
+    command.args(["--output-fd", "3", "dev"]);
+
+    // SAFETY: dup2 is async-signal-safe, so may be called between fork and exec
+    unsafe {
+        command.pre_exec(move || match libc::dup2(fd, 3) {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+
+    let output = command.output().await.unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+
+    let written = std::fs::read_to_string(fixture.path("fd-output")).unwrap();
+
+    assert!(
+        written.contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"),
+        "{}",
+        written
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn output_fd_must_be_open_before_fetching() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    let output = fixture.run(&sso.url(), &["--output-fd", "37", "dev"]).await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output)
+            .contains("unable to write to file descriptor 37: not an open file descriptor"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn overrides_the_profiles_sso_region() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--no-cache", "--sso-region", "cn-north-1", "dev"],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).starts_with("# role arn: arn:aws-cn:iam::123456789012:role/Developer\n"),
+        "{}",
+        stdout(&output)
+    );
+    // the endpoint url still takes precedence over the region
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn prefixes_every_variable_name() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--env-prefix", "MYAPP", "dev", "prod"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let exported_names: Vec<String> = stdout(&output)
+        .lines()
+        .filter_map(|line| line.strip_prefix("export "))
+        .map(|line| line.split('=').next().unwrap().to_string())
+        .collect();
+
+    assert!(exported_names.contains(&"MYAPP_AWS_ACCESS_KEY_ID_DEV".to_string()));
+    assert!(exported_names.contains(&"MYAPP_AWS_REGION_PROD".to_string()));
+    assert!(
+        exported_names
+            .iter()
+            .all(|name| name.starts_with("MYAPP_AWS_")),
+        "{:?}",
+        exported_names
+    );
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--env-prefix", "MYAPP", "--format", "fish", "dev"],
+        )
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("set -gx MYAPP_AWS_ACCESS_KEY_ID ASIADEV\n"));
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--env-prefix", "MYAPP", "--print-env-names", "dev"],
+        )
+        .await;
+
+    assert!(stdout(&output).starts_with("MYAPP_AWS_ACCESS_KEY_ID\n"));
+
+    let output = fixture
+        .run(&sso.url(), &["--env-prefix", "MYAPP", "unset", "dev"])
+        .await;
+
+    assert!(stdout(&output).starts_with("unset MYAPP_AWS_ACCESS_KEY_ID "));
+
+    for prefix in ["1APP", "MY-APP", ""] {
+        let output = fixture
+            .run(&sso.url(), &["--env-prefix", prefix, "dev"])
+            .await;
+
+        assert!(!output.status.success());
+        assert!(
+            stderr(&output).contains("not a valid variable name"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[tokio::test]
+async fn parses_human_readable_durations() {
+    // credentials which expire in half an hour
+    let expiration = (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        + 1800)
+        * 1000;
+    let sso = FakeSso::start(move |_| {
+        (
+            200,
+            format!(
+                r#"{{"roleCredentials":{{"accessKeyId":"ASIADEV","secretAccessKey":"dev-secret","sessionToken":"dev-session","expiration":{}}}}}"#,
+                expiration
+            ),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .run(&sso.url(), &["--no-cache", "--duration", "15m", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = fixture
+        .run(&sso.url(), &["--no-cache", "--duration", "1h", "dev"])
+        .await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("requested credentials valid for 3600 seconds"),
+        "{}",
+        stderr(&output)
+    );
+
+    for (duration, error) in [
+        ("0s", "duration must be between 15m and 12h"),
+        ("13h", "duration must be between 15m and 12h"),
+        ("-5m", "invalid duration '-5m'"),
+        ("an hour", "invalid duration 'an hour'"),
+        ("15m 500ms", "isn't a whole number of seconds"),
+    ] {
+        let output = fixture
+            .run(
+                &sso.url(),
+                &[format!("--duration={}", duration).as_str(), "dev"],
+            )
+            .await;
+
+        assert!(!output.status.success(), "{}", duration);
+        assert!(stderr(&output).contains(error), "{}", stderr(&output));
+    }
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--duration", "1h", "--duration-seconds", "3600", "dev"],
+        )
+        .await;
+
+    assert!(!output.status.success());
+    assert_eq!(sso.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn prints_errors_as_json() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_token("2020-01-01T00:00:00Z");
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["--error-format", "json", "--no-cache", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+
+    let error: serde_json::Value =
+        serde_json::from_str(stderr(&output).lines().last().unwrap()).unwrap();
+
+    assert_eq!(error["error"], "CacheTokenExpired");
+    assert_eq!(error["profile"], "dev");
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .contains("cached SSO token is expired"));
+
+    let output = fixture
+        .run(&sso.url(), &["--error-format", "json", "nonexistent"])
+        .await;
+
+    assert_eq!(output.status.code(), Some(2));
+
+    let error: serde_json::Value = serde_json::from_str(stderr(&output).trim()).unwrap();
+
+    assert_eq!(error["error"], "ProfileNotFound");
+    assert_eq!(error["profile"], "nonexistent");
+
+    let output = fixture
+        .run(
+            &sso.url(),
+            &["--error-format", "json", "dev", "nonexistent"],
+        )
+        .await;
+
+    assert_eq!(output.status.code(), Some(1));
+
+    let error: serde_json::Value = serde_json::from_str(stderr(&output).trim()).unwrap();
+
+    assert_eq!(error["error"], "ProfileFailures");
+    assert_eq!(error["failures"][0]["error"], "CacheTokenExpired");
+    assert_eq!(error["failures"][0]["profile"], "dev");
+    assert_eq!(error["failures"][1]["error"], "ProfileNotFound");
+}
+
+#[tokio::test]
+async fn overriding_the_account_requires_a_token_for_the_profiles_portal() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+    let token = std::fs::read_dir(fixture.cache_dir())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let contents = std::fs::read_to_string(&token).unwrap();
+
+    // a start url differing only in case and a trailing slash is the same portal
+    std::fs::write(
+        &token,
+        contents.replace(
+            "https://example.awsapps.com/start",
+            "https://EXAMPLE.awsapps.com/start/",
+        ),
+    )
+    .unwrap();
+
+    let output = fixture
+        .run(&sso.url(), &["--account-id", "210987654321", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    std::fs::write(
+        &token,
+        contents.replace(
+            "https://example.awsapps.com/start",
+            "https://other.awsapps.com/start",
+        ),
+    )
+    .unwrap();
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .args(["--account-id", "210987654321", "--refresh", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("issued for start url 'https://other.awsapps.com/start'"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn default_format_starts_with_the_env_format() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    let env = fixture.run(&sso.url(), &["--format", "env", "dev"]).await;
+    let default = fixture.run(&sso.url(), &["--no-comments", "dev"]).await;
+
+    assert!(env.status.success(), "{}", stderr(&env));
+    assert!(default.status.success(), "{}", stderr(&default));
+    assert!(
+        stdout(&default).starts_with(stdout(&env).as_str()),
+        "{}",
+        stdout(&default)
+    );
+    assert!(stdout(&default).contains("export AWS_REGION=us-east-1\n"));
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use aws_sdk_sso::{Client as SsoClient, Config as SsoConfig, Endpoint, Region};
+
+use aws_sso_env::credentials::EndpointVariant;
+use aws_sso_env::{
+    fetch_sso_credentials_with_client, CachedSsoToken, Error, FetchOptions, SsoProfile,
+};
+
+use common::{role_credentials, FakeSso};
+
+use time::OffsetDateTime;
+
+fn profile() -> SsoProfile {
+    SsoProfile {
+        profile_name: "dev".into(),
+        region: Some("us-east-1".into()),
+        output: None,
+        sso_account_id: Some("123456789012".into()),
+        sso_region: "us-east-1".into(),
+        sso_role_name: Some("Developer".into()),
+        sso_session: None,
+        sso_start_url: common::START_URL.into(),
+        properties: Default::default(),
+    }
+}
+
+fn token() -> CachedSsoToken {
+    CachedSsoToken {
+        access_token: "test-access-token".into(),
+        expires_at: OffsetDateTime::from_unix_timestamp(1_893_456_000).unwrap(),
+        region: "us-east-1".into(),
+        start_url: common::START_URL.into(),
+        refresh_token: None,
+        client_id: None,
+        client_secret: None,
+        cache_file: None,
+    }
+}
+
+fn client(sso: &FakeSso) -> SsoClient {
+    let config = SsoConfig::builder()
+        .region(Region::new("us-east-1"))
+        .endpoint_resolver(Endpoint::immutable(sso.url().parse().unwrap()))
+        .build();
+
+    SsoClient::from_conf(config)
+}
+
+#[tokio::test]
+async fn maps_role_credentials() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+
+    let credentials = fetch_sso_credentials_with_client(
+        &client(&sso),
+        &profile(),
+        &token(),
+        &FetchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(credentials.access_key_id, "ASIADEV");
+    assert_eq!(credentials.secret_access_key, "dev-secret");
+    assert_eq!(credentials.session_token, "dev-session");
+    assert_eq!(
+        credentials.expires_at.unix_timestamp_nanos(),
+        i128::from(common::EXPIRATION) * 1_000_000
+    );
+    assert_eq!(credentials.expiration().unwrap(), "2030-01-01T00:00:00Z");
+}
+
+#[tokio::test]
+async fn debug_formatting_masks_secrets() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+
+    let credentials = fetch_sso_credentials_with_client(
+        &client(&sso),
+        &profile(),
+        &token(),
+        &FetchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    for debug in [format!("{:?}", credentials), format!("{:#?}", credentials)] {
+        assert!(debug.contains("ASIADEV"), "{}", debug);
+        assert!(!debug.contains("dev-secret"), "{}", debug);
+        assert!(!debug.contains("dev-session"), "{}", debug);
+    }
+
+    assert!(format!("{:?}", credentials).contains(r#"secret_access_key: "***""#));
+}
+
+#[test]
+fn fips_endpoints_only_exist_outside_china_and_isolated_regions() {
+    let fips = EndpointVariant {
+        fips: true,
+        dualstack: false,
+    };
+
+    assert_eq!(
+        fips.endpoint_url("portal.sso", "us-gov-west-1").unwrap(),
+        Some("https://portal.sso-fips.us-gov-west-1.amazonaws.com".into())
+    );
+    assert_eq!(
+        fips.endpoint_url("sts", "eu-west-1").unwrap(),
+        Some("https://sts-fips.eu-west-1.amazonaws.com".into())
+    );
+
+    for region in ["cn-north-1", "us-iso-east-1", "us-isob-east-1"] {
+        assert!(matches!(
+            fips.endpoint_url("portal.sso", region),
+            Err(Error::EndpointVariantUnavailable { .. })
+        ));
+    }
+}
+
+#[test]
+fn dualstack_endpoints_use_the_partitions_dualstack_domain() {
+    let dualstack = EndpointVariant {
+        fips: false,
+        dualstack: true,
+    };
+
+    assert_eq!(
+        dualstack.endpoint_url("portal.sso", "eu-west-1").unwrap(),
+        Some("https://portal.sso.eu-west-1.api.aws".into())
+    );
+    assert_eq!(
+        dualstack.endpoint_url("portal.sso", "cn-north-1").unwrap(),
+        Some("https://portal.sso.cn-north-1.api.amazonwebservices.com.cn".into())
+    );
+    assert_eq!(
+        EndpointVariant {
+            fips: true,
+            dualstack: true,
+        }
+        .endpoint_url("portal.sso", "us-gov-west-1")
+        .unwrap(),
+        Some("https://portal.sso-fips.us-gov-west-1.api.aws".into())
+    );
+    assert!(dualstack
+        .endpoint_url("portal.sso", "us-iso-east-1")
+        .is_err());
+    assert_eq!(
+        EndpointVariant::default()
+            .endpoint_url("portal.sso", "eu-west-1")
+            .unwrap(),
+        None
+    );
+}
@@ -0,0 +1,294 @@
+// the stand-in keyring tool takes the arguments of `secret-tool`, not those of macOS's `security`
+#![cfg(all(unix, not(target_os = "macos")))]
+
+mod common;
+
+use common::{role_credentials, stderr, stdout, FakeSso, Fixture, START_URL};
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// The environment variable naming the keyring's command line tool.
+const KEYRING_COMMAND: &str = "AWS_SSO_ENV_KEYRING_COMMAND";
+
+/// Install a stand-in for `secret-tool` in the fixture, keeping each secret in a file of the `keyring` directory named
+/// after its service and account, and logging the arguments of every call to `argv`, and return its path. While a
+/// `locked` file exists, every call fails as a locked keyring does.
+fn fake_keyring(fixture: &Fixture) -> PathBuf {
+    let dir = fixture.path("keyring");
+
+    fixture.write(
+        "keyring-tool",
+        &format!(
+            r#"#!/bin/sh
+entry() {{ printf '%s/%s' '{}' "$(printf '%s-%s' "$1" "$2" | tr '/:' '__')"; }}
+printf '%s\n' "$@" >> '{}/argv'
+[ -e '{}/locked' ] && {{ echo 'secret-tool: Cannot unlock the keyring' >&2; exit 1; }}
+case "$1" in
+  lookup) file="$(entry "$3" "$5")"; [ -s "$file" ] || exit 1; cat "$file" ;;
+  store) mkdir -p '{}'; cat > "$(entry "$5" "$7")" ;;
+  *) exit 2 ;;
+esac
+"#,
+            dir.display(),
+            fixture.home.display(),
+            fixture.home.display(),
+            dir.display()
+        ),
+    );
+
+    let tool = fixture.path("keyring-tool");
+
+    std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    tool
+}
+
+#[tokio::test]
+async fn reads_the_token_from_the_keyring() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    fixture.write(
+        "keyring/aws-sso-env-example",
+        &format!(
+            r#"{{"accessToken":"keyring-access-token","expiresAt":"2030-01-01T00:00:00Z","region":"us-east-1","startUrl":"{}"}}"#,
+            START_URL
+        ),
+    );
+
+    let output = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, tool)
+        .args(["--token-source", "keyring", "--no-cache", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    assert_eq!(
+        sso.requests()[0].bearer_token.as_deref(),
+        Some("keyring-access-token")
+    );
+}
+
+#[tokio::test]
+async fn fails_when_the_keyring_has_no_token() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    let output = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, tool)
+        .args(["--token-source", "keyring", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    // the token cached in a file is ignored
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn reports_a_locked_keyring_rather_than_a_missing_token() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    fixture.write("locked", "");
+
+    let output = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, tool)
+        .args(["--token-source", "keyring", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Cannot unlock the keyring"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn reports_an_unreadable_keyring_token() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    fixture.write("keyring/aws-sso-env-example", "not a token");
+
+    let output = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, tool)
+        .args(["--token-source", "keyring", "check", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "profile ok\ntoken unreadable\n");
+}
+
+#[tokio::test]
+async fn stores_credentials_in_the_keyring_and_loads_them() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    let stored = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, &tool)
+        .args(["store", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(stored.status.success(), "{}", stderr(&stored));
+    assert_eq!(stdout(&stored), "");
+
+    let secret =
+        std::fs::read_to_string(fixture.path("keyring/aws-sso-env-credentials-dev")).unwrap();
+    let secret: serde_json::Value = serde_json::from_str(secret.as_str()).unwrap();
+
+    assert_eq!(secret["accessKeyId"], "ASIADEV");
+    assert_eq!(secret["accountId"], "123456789012");
+
+    let loaded = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, &tool)
+        .args(["--format", "fish", "load", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(loaded.status.success(), "{}", stderr(&loaded));
+
+    let stdout = stdout(&loaded);
+
+    assert!(
+        stdout.contains("set -gx AWS_ACCESS_KEY_ID ASIADEV\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("set -gx AWS_REGION us-east-1\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("role/Developer"), "{}", stdout);
+
+    // loading never calls SSO
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn fails_to_load_credentials_never_stored() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    let output = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, tool)
+        .args(["load", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("run `aws-sso-env store dev`"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn never_passes_secrets_as_arguments() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+    let tool = fake_keyring(&fixture);
+
+    let stored = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, &tool)
+        .args(["store", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(stored.status.success(), "{}", stderr(&stored));
+
+    // the secret reached the keyring, but not through the tool's arguments, which any local user can read
+    let secret =
+        std::fs::read_to_string(fixture.path("keyring/aws-sso-env-credentials-dev")).unwrap();
+    let argv = std::fs::read_to_string(fixture.path("argv")).unwrap();
+
+    assert!(secret.contains("dev-secret"));
+    assert!(argv.contains("aws-sso-env-credentials"), "{}", argv);
+    assert!(!argv.contains("dev-secret"), "{}", argv);
+    assert!(!argv.contains("dev-session"), "{}", argv);
+    assert!(!argv.contains("ASIADEV"), "{}", argv);
+}
+
+#[tokio::test]
+async fn a_missing_keyring_tool_is_not_a_missing_token() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+    let tool = fixture.path("no-such-keyring-tool");
+
+    for args in [
+        &["--token-source", "keyring", "dev"][..],
+        &["--token-source", "keyring", "check", "dev"][..],
+    ] {
+        let output = fixture
+            .command(&sso.url())
+            .env(KEYRING_COMMAND, &tool)
+            .args(args)
+            .output()
+            .await
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+        assert!(
+            stderr(&output).contains("no-such-keyring-tool': not found on the PATH"),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    let output = fixture
+        .command(&sso.url())
+        .env(KEYRING_COMMAND, &tool)
+        .args(["--token-source", "keyring", "check", "--status-only", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(sso.requests().is_empty());
+}
@@ -0,0 +1,533 @@
+mod common;
+
+use common::{role_credentials, stderr, stdout, FakeSso, Fixture};
+
+fn config(account_id: &str) -> String {
+    format!(
+        "[profile dev]\n\
+         sso_session = example ; the company portal\n\
+         sso_account_id = {}\n\
+         sso_role_name = Developer   # read-write\n\
+         region = us-east-1\n\
+         \n\
+         [sso-session example]\n\
+         sso_start_url = {}\n\
+         sso_region = us-east-1\n",
+        account_id,
+        common::START_URL
+    )
+}
+
+#[tokio::test]
+async fn strips_inline_comments_from_values() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(".aws/config", &config("123456789012 # dev account"));
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sso.requests()[0].path,
+        "/federation/credentials?role_name=Developer&account_id=123456789012"
+    );
+}
+
+#[tokio::test]
+async fn rejects_malformed_account_id() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(".aws/config", &config("1234-5678-9012"));
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("not a 12-digit account id"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn rejects_start_url_other_than_https() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    for start_url in [
+        "htps://example.awsapps.com/start",
+        "example.awsapps.com/start",
+        "http://example.awsapps.com",
+    ] {
+        fixture.write(
+            ".aws/config",
+            &config("123456789012").replace(common::START_URL, start_url),
+        );
+
+        let output = fixture.run(&sso.url(), &["check", "dev"]).await;
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(
+            stderr(&output).contains("sso_start_url"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[tokio::test]
+async fn inherits_from_source_profiles() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(
+        ".aws/config",
+        &format!(
+            "{}\n\
+             [profile base]\n\
+             source_profile = dev\n\
+             sso_role_name = ReadOnly\n\
+             \n\
+             [profile derived]\n\
+             source_profile = base\n\
+             role_arn = arn:aws:iam::210987654321:role/Deployer\n\
+             region = eu-west-1\n",
+            config("123456789012")
+        ),
+    );
+
+    let output = fixture.run(&sso.url(), &["derived"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_REGION=eu-west-1\n"));
+    assert_eq!(
+        sso.requests()[0].path,
+        "/federation/credentials?role_name=ReadOnly&account_id=123456789012"
+    );
+}
+
+#[tokio::test]
+async fn finds_profiles_named_with_spaces_and_unicode() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(
+        ".aws/config",
+        &format!(
+            "[profile my sso profile]\n\
+             sso_session = example\n\
+             sso_account_id = 123456789012\n\
+             sso_role_name = Developer\n\
+             \n\
+             [profile  développeur ñ]\n\
+             source_profile = my sso profile\n\
+             sso_role_name = ReadOnly\n\
+             \n\
+             [profile my%20sso%20profile]\n\
+             sso_session = example\n\
+             sso_account_id = 210987654321\n\
+             sso_role_name = Deployer\n\
+             \n\
+             [sso-session example]\n\
+             sso_start_url = {}\n\
+             sso_region = us-east-1\n",
+            common::START_URL
+        ),
+    );
+
+    for (profile_name, path) in [
+        (
+            "my sso profile",
+            "/federation/credentials?role_name=Developer&account_id=123456789012",
+        ),
+        (
+            "développeur ñ",
+            "/federation/credentials?role_name=ReadOnly&account_id=123456789012",
+        ),
+        (
+            "my%20sso%20profile",
+            "/federation/credentials?role_name=Deployer&account_id=210987654321",
+        ),
+    ] {
+        let output = fixture.run(&sso.url(), &["--no-cache", profile_name]).await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(sso.requests().last().unwrap().path, path);
+    }
+}
+
+#[tokio::test]
+async fn rejects_source_profile_cycles() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(
+        ".aws/config",
+        "[profile a]\nsource_profile = b\n\n[profile b]\nsource_profile = a\n",
+    );
+
+    let output = fixture.run(&sso.url(), &["a"]).await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("profiles form a cycle: a -> b -> a"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+const SSO_ENV: &[(&str, &str)] = &[
+    ("AWS_SSO_START_URL", common::START_URL),
+    ("AWS_SSO_REGION", "us-east-1"),
+    ("AWS_SSO_ACCOUNT_ID", "333344445555"),
+    ("AWS_SSO_ROLE_NAME", "Builder"),
+    ("AWS_REGION", "ap-southeast-2"),
+];
+
+#[tokio::test]
+async fn reads_profile_from_env() {
+    let sso = FakeSso::start(|_| (200, role_credentials("ASIACI", "ci-secret", "ci-session")));
+    let fixture = Fixture::new();
+
+    fixture.write(".aws/config", "");
+
+    let output = fixture
+        .command(&sso.url())
+        .envs(SSO_ENV.iter().copied())
+        .arg("--from-env")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_REGION=ap-southeast-2\n"));
+    assert_eq!(
+        sso.requests()[0].path,
+        "/federation/credentials?role_name=Builder&account_id=333344445555"
+    );
+}
+
+#[tokio::test]
+async fn caches_credentials_from_env_for_each_account() {
+    let sso = FakeSso::start_routed(|path| {
+        let account_id = path.rsplit('=').next().unwrap().to_string();
+
+        (
+            200,
+            role_credentials(&format!("ASIA{}", account_id), "secret", "session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(".aws/config", "");
+
+    for account_id in ["111111111111", "222222222222"] {
+        let output = fixture
+            .command(&sso.url())
+            .envs(SSO_ENV.iter().copied())
+            .env("AWS_SSO_ACCOUNT_ID", account_id)
+            .args(["--from-env", "--format", "json"])
+            .output()
+            .await
+            .unwrap();
+
+        assert!(output.status.success(), "{}", stderr(&output));
+
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        assert_eq!(json["accountId"], account_id);
+        assert_eq!(json["accessKeyId"], format!("ASIA{}", account_id));
+    }
+
+    assert_eq!(sso.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn falls_back_to_env_for_unknown_profiles() {
+    let sso = FakeSso::start(|_| (200, role_credentials("ASIACI", "ci-secret", "ci-session")));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .envs(SSO_ENV.iter().copied())
+        .arg("ci")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(sso.requests()[0].path.contains("account_id=333344445555"));
+}
+
+#[tokio::test]
+async fn reading_profile_from_env_requires_every_variable() {
+    let sso = FakeSso::start(|_| (200, role_credentials("ASIACI", "ci-secret", "ci-session")));
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .envs(
+            SSO_ENV
+                .iter()
+                .copied()
+                .filter(|(name, _)| *name != "AWS_SSO_ROLE_NAME"),
+        )
+        .arg("--from-env")
+        .output()
+        .await
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("environment variable AWS_SSO_ROLE_NAME must be set"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn expands_home_in_config_path_with_platform_separator() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+    let separator = std::path::MAIN_SEPARATOR;
+
+    let output = fixture
+        .command(&sso.url())
+        .env(
+            "AWS_CONFIG_FILE",
+            format!("~{separator}.aws{separator}config"),
+        )
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(sso.requests()[0].path.contains("account_id=123456789012"));
+}
+
+#[cfg(windows)]
+#[tokio::test]
+async fn finds_home_from_userprofile_on_windows() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env_remove("HOME")
+        .env("USERPROFILE", fixture.path(""))
+        .env("AWS_CONFIG_FILE", r"~\.aws\config")
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn region_is_optional() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(
+        ".aws/config",
+        &config("123456789012").replace("region = us-east-1\n\n", "\n"),
+    );
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+    let stdout = stdout(&output);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout.contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("AWS_REGION"), "{}", stdout);
+    assert!(!stdout.contains("AWS_DEFAULT_REGION"), "{}", stdout);
+}
+
+#[tokio::test]
+async fn takes_region_but_not_sso_keys_from_the_default_profile() {
+    let sso = FakeSso::start(|_| {
+        (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(
+        ".aws/config",
+        &format!(
+            "[default]\n\
+             region = eu-central-1\n\
+             sso_account_id = 210987654321\n\
+             sso_role_name = Administrator\n\
+             \n\
+             {}\n\
+             [profile other]\n\
+             sso_session = example\n",
+            config("123456789012").replace("region = us-east-1\n\n", "\n")
+        ),
+    );
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("export AWS_REGION=eu-central-1\n"),
+        "{}",
+        stdout(&output)
+    );
+    assert_eq!(
+        sso.requests()[0].path,
+        "/federation/credentials?role_name=Developer&account_id=123456789012"
+    );
+
+    // a profile without an account and role of its own doesn't take those of the default profile
+    let output = fixture.run(&sso.url(), &["other"]).await;
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("sso_account_id"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn caches_credentials_for_similarly_named_profiles_apart() {
+    // the access key id names the account, to tell apart whose credentials are exported
+    let sso = FakeSso::start_routed(|path| {
+        let account_id = path.rsplit('=').next().unwrap();
+
+        (
+            200,
+            role_credentials(&format!("ASIA{}", account_id), "secret", "session"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write(
+        ".aws/config",
+        &format!(
+            "[profile my sso]\n\
+             sso_session = example\n\
+             sso_account_id = 111111111111\n\
+             sso_role_name = Developer\n\
+             \n\
+             [profile my_sso]\n\
+             sso_session = example\n\
+             sso_account_id = 222222222222\n\
+             sso_role_name = Developer\n\
+             \n\
+             [profile prod-é]\n\
+             sso_session = example\n\
+             sso_account_id = 333333333333\n\
+             sso_role_name = Developer\n\
+             \n\
+             [profile prod-ü]\n\
+             sso_session = example\n\
+             sso_account_id = 444444444444\n\
+             sso_role_name = Developer\n\
+             \n\
+             [sso-session example]\n\
+             sso_start_url = {}\n\
+             sso_region = us-east-1\n",
+            common::START_URL
+        ),
+    );
+
+    let profiles = [
+        ("my sso", "111111111111"),
+        ("my_sso", "222222222222"),
+        ("prod-é", "333333333333"),
+        ("prod-ü", "444444444444"),
+    ];
+
+    for (profile_name, _) in profiles {
+        let output = fixture.run(&sso.url(), &[profile_name]).await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    // each profile has credentials cached of its own, rather than those of the profile last fetched
+    for (profile_name, account_id) in profiles {
+        let output = fixture.run(&sso.url(), &["--offline", profile_name]).await;
+
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(
+            stdout(&output).contains(&format!("export AWS_ACCESS_KEY_ID=ASIA{}\n", account_id)),
+            "{}: {}",
+            profile_name,
+            stdout(&output)
+        );
+    }
+
+    // as are the credentials of an account overridden, even for a profile named as the override would be keyed
+    let output = fixture
+        .run(&sso.url(), &["--account-id", "555555555555", "my_sso"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fixture
+        .cache_dir()
+        .join("aws-sso-env-my_sso-847ba4db05bbff2be5f02cb777416a80421405ab.json")
+        .exists());
+    assert!(fixture
+        .cache_dir()
+        .join("aws-sso-env-my%20sso-dedb6d11a01e41dcc8551646c93ea9be7cc28557.json")
+        .exists());
+    assert_eq!(sso.requests().len(), 5);
+}
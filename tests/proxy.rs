@@ -0,0 +1,309 @@
+mod common;
+
+use common::{stderr, stdout, FakeSso, Fixture};
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A local proxy tunnelling every `CONNECT` to the requested address, recording the request lines and headers it
+/// receives.
+struct FakeProxy {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    headers: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeProxy {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let headers = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = requests.clone();
+        let recorded_headers = headers.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let recorded_headers = recorded_headers.clone();
+
+                tokio::spawn(async move {
+                    let mut client = BufReader::new(stream);
+                    let mut request_line = String::new();
+
+                    client.read_line(&mut request_line).await.unwrap();
+
+                    loop {
+                        let mut header = String::new();
+
+                        client.read_line(&mut header).await.unwrap();
+
+                        if header == "\r\n" {
+                            break;
+                        }
+
+                        recorded_headers
+                            .lock()
+                            .unwrap()
+                            .push(header.trim_end().into());
+                    }
+
+                    let target = request_line.split_whitespace().nth(1).unwrap().to_string();
+
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push(request_line.trim_end().into());
+
+                    let mut server = TcpStream::connect(target).await.unwrap();
+                    let mut client = client.into_inner();
+
+                    client
+                        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                        .await
+                        .unwrap();
+
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                });
+            }
+        });
+
+        Self {
+            addr,
+            requests,
+            headers,
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn headers(&self) -> Vec<String> {
+        self.headers.lock().unwrap().clone()
+    }
+}
+
+#[tokio::test]
+async fn connects_through_proxy() {
+    let sso = FakeSso::with_dev_credentials();
+    let proxy = FakeProxy::start().await;
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", proxy.url())
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+    assert_eq!(
+        proxy.requests(),
+        vec![format!("CONNECT {} HTTP/1.1", sso.addr)]
+    );
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn bypasses_proxy_for_no_proxy_hosts() {
+    let sso = FakeSso::with_dev_credentials();
+    let proxy = FakeProxy::start().await;
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("http_proxy", proxy.url())
+        .env("no_proxy", "example.com, 127.0.0.1")
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(proxy.requests().is_empty());
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn authenticates_with_proxy_credentials() {
+    let sso = FakeSso::with_dev_credentials();
+    let proxy = FakeProxy::start().await;
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", format!("http://user:p%40ss@{}", proxy.addr))
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        proxy
+            .headers()
+            .contains(&"Proxy-Authorization: Basic dXNlcjpwQHNz".to_string()),
+        "{:?}",
+        proxy.headers()
+    );
+}
+
+#[tokio::test]
+async fn sends_no_credentials_to_proxy_without_them() {
+    let sso = FakeSso::with_dev_credentials();
+    let proxy = FakeProxy::start().await;
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", proxy.url())
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(proxy.headers(), vec![format!("Host: {}", sso.addr)]);
+}
+
+#[tokio::test]
+async fn bypasses_proxy_for_no_proxy_hosts_on_their_port() {
+    let sso = FakeSso::with_dev_credentials();
+    let proxy = FakeProxy::start().await;
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", proxy.url())
+        .env("NO_PROXY", sso.addr.to_string())
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(proxy.requests().is_empty());
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn proxies_no_proxy_hosts_on_other_ports() {
+    let sso = FakeSso::with_dev_credentials();
+    let proxy = FakeProxy::start().await;
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", proxy.url())
+        .env(
+            "NO_PROXY",
+            format!("{}:{}", sso.addr.ip(), sso.addr.port().wrapping_add(1)),
+        )
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        proxy.requests(),
+        vec![format!("CONNECT {} HTTP/1.1", sso.addr)]
+    );
+}
+
+#[tokio::test]
+async fn reports_unreachable_proxy() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    // nothing listens on the port once the listener is dropped
+    let unused = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", unused.to_string())
+        .args(["--max-retries", "0", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains(&format!(
+            "unable to connect through proxy http://{}/",
+            unused
+        )),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
+
+#[tokio::test]
+async fn hides_proxy_credentials_in_errors() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    let unused = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let output = fixture
+        .logging_command(&sso.url())
+        .env("HTTP_PROXY", format!("http://user:secret@{}", unused))
+        .args(["--max-retries", "0", "-vv", "dev"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains(&format!(
+            "unable to connect through proxy http://{}/",
+            unused
+        )),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stderr(&output).contains("secret"), "{}", stderr(&output));
+}
+
+#[tokio::test]
+async fn hides_proxy_credentials_in_unparseable_urls() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    let output = fixture
+        .command(&sso.url())
+        .env("HTTP_PROXY", "http://user:se cret@proxy.example.com:3128")
+        .arg("dev")
+        .output()
+        .await
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("proxy url in HTTP_PROXY"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stderr(&output).contains("se cret"), "{}", stderr(&output));
+    assert!(sso.requests().is_empty());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::{error_body, role_credentials, stderr, stdout, FakeSso, Fixture};
+
+fn create_token(access_token: &str, refresh_token: &str) -> String {
+    format!(
+        r#"{{"accessToken":"{}","tokenType":"Bearer","expiresIn":28800,"refreshToken":"{}"}}"#,
+        access_token, refresh_token
+    )
+}
+
+#[tokio::test]
+async fn refreshes_expired_token() {
+    let sso = FakeSso::start(|index| match index {
+        0 => (
+            200,
+            create_token("refreshed-access-token", "rotated-refresh-token"),
+        ),
+        _ => (
+            200,
+            role_credentials("ASIADEV", "dev-secret", "dev-session"),
+        ),
+    });
+    let fixture = Fixture::new();
+
+    fixture.write_refreshable_token("2020-01-01T00:00:00Z");
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIADEV\n"));
+
+    let requests = sso.requests();
+
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].path, "/token");
+    assert_eq!(
+        requests[1].bearer_token.as_deref(),
+        Some("refreshed-access-token")
+    );
+
+    let token = fixture.read_token();
+
+    assert_eq!(token["accessToken"], "refreshed-access-token");
+    assert_eq!(token["refreshToken"], "rotated-refresh-token");
+    assert_eq!(token["clientId"], "test-client-id");
+    assert_eq!(token["registrationExpiresAt"], "2030-01-01T00:00:00Z");
+    assert!(token["expiresAt"].as_str().unwrap() > "2020-01-01T00:00:00Z");
+}
+
+#[tokio::test]
+async fn failed_refresh_exits_as_expired() {
+    let sso = FakeSso::start(|_| {
+        (
+            400,
+            error_body("InvalidGrantException", "Invalid refresh token"),
+        )
+    });
+    let fixture = Fixture::new();
+
+    fixture.write_refreshable_token("2020-01-01T00:00:00Z");
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert_eq!(sso.requests().len(), 1);
+    assert_eq!(fixture.read_token()["accessToken"], "test-access-token");
+}
+
+#[tokio::test]
+async fn expired_token_without_refresh_token_exits_as_expired() {
+    let sso = FakeSso::start(|_| (500, String::new()));
+    let fixture = Fixture::new();
+
+    fixture.write_token("2020-01-01T00:00:00Z");
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(sso.requests().is_empty());
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use aws_sso_env::settings::Settings;
+
+use common::{stderr, stdout, FakeSso, Fixture};
+
+use std::path::PathBuf;
+
+const SETTINGS_FILE: &str = ".config/aws-sso-env/config.toml";
+
+#[tokio::test]
+async fn parses_settings() {
+    let fixture = Fixture::new();
+
+    fixture.write(
+        SETTINGS_FILE,
+        "# shared by the team\n\
+         format = \"fish\"\n\
+         expiry-margin = 120 # two minutes\n\
+         token-cache-dir = ['/tmp/leapp', \"/tmp/vault\",]\n\
+         no-comments = true\n",
+    );
+
+    let settings = Settings::load(&fixture.path(SETTINGS_FILE)).await.unwrap();
+
+    assert_eq!(
+        settings,
+        Settings {
+            format: Some("fish".into()),
+            expiry_margin: Some(120),
+            token_cache_dir: vec![PathBuf::from("/tmp/leapp"), PathBuf::from("/tmp/vault")],
+            no_comments: true,
+            ..Settings::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn missing_settings_file_is_the_defaults() {
+    let fixture = Fixture::new();
+
+    let settings = Settings::load(&fixture.path(SETTINGS_FILE)).await.unwrap();
+
+    assert_eq!(settings, Settings::default());
+}
+
+#[tokio::test]
+async fn rejects_unknown_settings_and_tables() {
+    let fixture = Fixture::new();
+
+    for (contents, reason) in [
+        ("formt = \"fish\"\n", "unknown field `formt`"),
+        (
+            "[defaults]\nformat = \"fish\"\n",
+            "line 1: tables are not supported",
+        ),
+        ("format = fish\n", "line 1: unsupported value `fish`"),
+        (
+            "timeout = 5 10\n",
+            "line 1: unexpected `10` after the value",
+        ),
+    ] {
+        fixture.write(SETTINGS_FILE, contents);
+
+        let e = Settings::load(&fixture.path(SETTINGS_FILE))
+            .await
+            .unwrap_err()
+            .to_string();
+
+        assert!(e.contains(reason), "{}", e);
+    }
+}
+
+#[tokio::test]
+async fn settings_are_defaults_overridden_by_flags() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    fixture.write(SETTINGS_FILE, "format = \"fish\"\nno-comments = true\n");
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).starts_with("set -gx AWS_ACCESS_KEY_ID ASIADEV"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = fixture
+        .run(&sso.url(), &["--format", "dotenv", "dev"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).starts_with("AWS_ACCESS_KEY_ID=ASIADEV"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = fixture.run(&sso.url(), &["--json", "dev"]).await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with('{'), "{}", stdout(&output));
+}
+
+#[tokio::test]
+async fn reports_invalid_settings() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    fixture.write(SETTINGS_FILE, "format = \"csv\"\n");
+
+    let output = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("output format 'csv'"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sso.requests().is_empty());
+}
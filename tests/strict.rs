@@ -0,0 +1,116 @@
+mod common;
+
+use common::{stderr, stdout, FakeSso, Fixture};
+
+#[tokio::test]
+async fn requires_a_region() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    let config = std::fs::read_to_string(fixture.path(".aws/config")).unwrap();
+    fixture.write(
+        ".aws/config",
+        &config.replacen("\nregion = us-east-1\n", "\n", 1),
+    );
+
+    let lenient = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(lenient.status.success(), "{}", stderr(&lenient));
+    assert!(!stdout(&lenient).contains("AWS_REGION"));
+
+    let strict = fixture.run(&sso.url(), &["--strict", "dev"]).await;
+
+    assert!(!strict.status.success());
+    assert!(stdout(&strict).is_empty());
+    assert!(
+        stderr(&strict).contains("profile 'dev' has no region"),
+        "{}",
+        stderr(&strict)
+    );
+
+    let unregioned = fixture
+        .run(&sso.url(), &["--strict", "--no-region", "dev"])
+        .await;
+
+    assert!(unregioned.status.success(), "{}", stderr(&unregioned));
+}
+
+#[tokio::test]
+async fn requires_passthrough_properties() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    let args = ["--passthrough-key", "cli_pager=AWS_PAGER", "dev"];
+
+    let lenient = fixture.run(&sso.url(), &args).await;
+
+    assert!(lenient.status.success(), "{}", stderr(&lenient));
+    assert!(!stdout(&lenient).contains("AWS_PAGER"));
+
+    let strict = fixture
+        .run(&sso.url(), &[&["--strict"][..], &args].concat())
+        .await;
+
+    assert!(!strict.status.success());
+    assert!(
+        stderr(&strict).contains("profile 'dev' does not set cli_pager"),
+        "{}",
+        stderr(&strict)
+    );
+}
+
+#[tokio::test]
+async fn requires_readable_cached_credentials() {
+    let sso = FakeSso::with_dev_credentials();
+    let fixture = Fixture::new();
+
+    std::fs::write(fixture.dev_credentials_cache_file(), "{not json").unwrap();
+
+    let strict = fixture.run(&sso.url(), &["--strict", "dev"]).await;
+
+    assert!(!strict.status.success());
+    assert!(
+        stderr(&strict).contains("unable to parse cached SSO credentials"),
+        "{}",
+        stderr(&strict)
+    );
+    assert!(sso.requests().is_empty());
+
+    let lenient = fixture.run(&sso.url(), &["dev"]).await;
+
+    assert!(lenient.status.success(), "{}", stderr(&lenient));
+    assert_eq!(sso.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn requires_the_account_alias() {
+    let sso = FakeSso::with_dev_credentials();
+    let iam = FakeSso::start(|_| {
+        (
+            403,
+            "<ErrorResponse><Error><Code>AccessDenied</Code></Error></ErrorResponse>".into(),
+        )
+    });
+    let fixture = Fixture::new();
+
+    let strict = fixture
+        .run(
+            &sso.url(),
+            &[
+                "--iam-endpoint-url",
+                iam.url().as_str(),
+                "--print-account-alias",
+                "--strict",
+                "dev",
+            ],
+        )
+        .await;
+
+    assert!(!strict.status.success());
+    assert!(stdout(&strict).is_empty());
+    assert!(
+        stderr(&strict).contains("not permitted to call IAM ListAccountAliases"),
+        "{}",
+        stderr(&strict)
+    );
+}
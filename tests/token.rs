@@ -0,0 +1,111 @@
+use aws_sso_env::CachedSsoToken;
+
+use time::OffsetDateTime;
+
+/// 2030-01-01T00:00:00Z.
+const EXPIRES_AT: i64 = 1_893_456_000;
+
+fn token_expiring(expires_at: &str) -> serde_json::Result<CachedSsoToken> {
+    serde_json::from_str(&format!(
+        r#"{{"accessToken":"token","expiresAt":{},"region":"us-east-1","startUrl":"https://example.awsapps.com/start"}}"#,
+        expires_at
+    ))
+}
+
+#[test]
+fn parses_rfc3339_expiry() {
+    let token = token_expiring(r#""2030-01-01T00:00:00Z""#).unwrap();
+
+    assert_eq!(token.expires_at.unix_timestamp(), EXPIRES_AT);
+}
+
+#[test]
+fn parses_rfc3339_expiry_with_offset() {
+    let token = token_expiring(r#""2030-01-01T02:00:00+02:00""#).unwrap();
+
+    assert_eq!(token.expires_at.unix_timestamp(), EXPIRES_AT);
+}
+
+#[test]
+fn parses_epoch_seconds_expiry() {
+    let token = token_expiring(&EXPIRES_AT.to_string()).unwrap();
+
+    assert_eq!(
+        token.expires_at,
+        OffsetDateTime::from_unix_timestamp(EXPIRES_AT).unwrap()
+    );
+}
+
+#[test]
+fn parses_epoch_milliseconds_expiry() {
+    let token = token_expiring(&(EXPIRES_AT * 1000 + 250).to_string()).unwrap();
+
+    assert_eq!(token.expires_at.unix_timestamp(), EXPIRES_AT);
+    assert_eq!(token.expires_at.millisecond(), 250);
+}
+
+#[test]
+fn rejects_unparseable_expiry() {
+    assert!(token_expiring(r#""next tuesday""#).is_err());
+    assert!(token_expiring("true").is_err());
+}
+
+#[test]
+fn parses_refresh_token_and_client() {
+    let token: CachedSsoToken = serde_json::from_str(
+        r#"{
+            "startUrl": "https://example.awsapps.com/start",
+            "region": "us-east-1",
+            "accessToken": "token",
+            "expiresAt": "2030-01-01T00:00:00Z",
+            "clientId": "client-id",
+            "clientSecret": "client-secret",
+            "registrationExpiresAt": "2030-03-01T00:00:00Z",
+            "refreshToken": "refresh-token"
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(token.refresh_token.as_deref(), Some("refresh-token"));
+    assert_eq!(token.client_id.as_deref(), Some("client-id"));
+    assert_eq!(token.client_secret.as_deref(), Some("client-secret"));
+}
+
+#[test]
+fn refresh_token_and_client_are_optional() {
+    let token = token_expiring(r#""2030-01-01T00:00:00Z""#).unwrap();
+
+    assert!(token.refresh_token.is_none());
+    assert!(token.client_id.is_none());
+    assert!(token.client_secret.is_none());
+}
+
+#[test]
+fn debug_formatting_masks_secrets() {
+    let token: CachedSsoToken = serde_json::from_str(
+        r#"{
+            "accessToken": "access-token",
+            "expiresAt": "2030-01-01T00:00:00Z",
+            "region": "us-east-1",
+            "startUrl": "https://example.awsapps.com/start",
+            "clientId": "client-id",
+            "clientSecret": "client-secret",
+            "refreshToken": "refresh-token"
+        }"#,
+    )
+    .unwrap();
+
+    for debug in [format!("{:?}", token), format!("{:#?}", token)] {
+        assert!(!debug.contains("access-token"), "{}", debug);
+        assert!(!debug.contains("client-secret"), "{}", debug);
+        assert!(!debug.contains("refresh-token"), "{}", debug);
+        assert!(
+            debug.contains("https://example.awsapps.com/start"),
+            "{}",
+            debug
+        );
+    }
+
+    assert!(format!("{:?}", token).contains(r#"access_token: "***""#));
+    assert!(format!("{:?}", token).contains(r#"refresh_token: Some("***")"#));
+}
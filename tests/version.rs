@@ -0,0 +1,39 @@
+use std::process::Command;
+
+fn version(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-env"))
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_version_with_build_metadata() {
+    let stdout = version(&["--version"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert_eq!(lines.len(), 3, "{}", stdout);
+    assert_eq!(
+        lines[0],
+        format!("aws-sso-env {}", env!("CARGO_PKG_VERSION"))
+    );
+    assert!(lines[1].starts_with("commit: "), "{}", stdout);
+    assert!(lines[2].starts_with("built: "), "{}", stdout);
+    assert!(lines[2].ends_with('Z'), "{}", stdout);
+}
+
+#[test]
+fn prints_sdk_versions_when_verbose() {
+    let stdout = version(&["-V", "--verbose"]);
+
+    assert!(
+        stdout.lines().any(|line| line.starts_with("aws-sdk-sso ")),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains(" unknown"), "{}", stdout);
+}